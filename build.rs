@@ -1,5 +1,10 @@
 use std::{fs, io::Write};
 
+include!("src/webview/bindings.rs");
+
+/// Where the TypeScript declarations for the bindings are kept, for editors.
+const STUBS: &str = "res/bindings.d.ts";
+
 fn main() {
     println!("cargo:rerun-if-changed=res/walltaker-engine.rc");
     embed_resource::compile("res/walltaker-engine.rc", embed_resource::NONE);
@@ -9,6 +14,105 @@ fn main() {
 
     minify("res/background.html", &format!("{out_dir}/background.html.min"));
     minify("res/settings.html", &format!("{out_dir}/settings.html.min"));
+
+    println!("cargo:rerun-if-changed=src/webview/bindings.rs");
+    check_bindings("res/settings.html", SETTINGS);
+    check_bindings("res/background.html", BACKGROUND);
+    check_stubs(&stubs(&[("settings.html", SETTINGS), ("background.html and themes", BACKGROUND)]));
+}
+
+/// Things on `window` that come from the browser rather than from us.
+const BROWSER_FUNCTIONS: &[&str] = &[
+    "addEventListener", "removeEventListener", "setTimeout", "clearTimeout",
+    "setInterval", "clearInterval", "requestAnimationFrame", "alert", "confirm",
+];
+
+/// Fail the build if `page` calls a `window.something(` that isn't in
+/// `bindings`.
+fn check_bindings(page: &str, bindings: &[Binding]) {
+    let declared: Vec<&str> = bindings.iter().map(|(name, ..)| *name).collect();
+
+    let page_src = fs::read_to_string(page).unwrap();
+    for call in page_src.split("window.").skip(1) {
+        let name: String = call.chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .collect();
+        let is_call = call[name.len()..].starts_with('(')
+            && !BROWSER_FUNCTIONS.contains(&name.as_str());
+
+        assert!(!is_call || declared.contains(&name.as_str()),
+            "{page} calls window.{name}() but nothing by that name is declared in \
+            src/webview/bindings.rs.");
+    }
+}
+
+/// Fail the build if [`STUBS`] is out of date, or bring it up to date if
+/// `UPDATE_BINDINGS` is set.
+fn check_stubs(expected: &str) {
+    println!("cargo:rerun-if-changed={STUBS}");
+    println!("cargo:rerun-if-env-changed=UPDATE_BINDINGS");
+
+    if std::env::var_os("UPDATE_BINDINGS").is_some() {
+        fs::write(STUBS, expected).unwrap();
+        return;
+    }
+
+    let current = fs::read_to_string(STUBS).unwrap_or_default();
+    assert!(current.replace("\r\n", "\n") == expected,
+        "{STUBS} doesn't match src/webview/bindings.rs. Build with UPDATE_BINDINGS=1 \
+        set to regenerate it.");
+}
+
+/// TypeScript declarations for each page's `bindings`.
+fn stubs(pages: &[(&str, &[Binding])]) -> String {
+    let mut stubs = String::from("// Generated by build.rs from src/webview/bindings.rs.\n");
+    for (page, bindings) in pages {
+        stubs.push_str(&format!("\n// {page}\n"));
+        for (name, params, returns) in *bindings {
+            let params: Vec<_> = params.iter()
+                .map(|(param, rust)| format!("{param}: {}", typescript(rust)))
+                .collect();
+            stubs.push_str(&format!("declare function {name}({}): Promise<{}>;\n",
+                params.join(", "), typescript(returns)));
+        }
+    }
+
+    stubs
+}
+
+/// What the page sees for our own types.
+const TYPESCRIPT_NAMES: &[(&str, &str)] = &[
+    ("Config", "object"),
+    ("FieldError", "{ field: string, message: string }"),
+    ("ImportMode", "'Merge' | 'Replace'"),
+    ("Monitor", "object"),
+    ("Value", "any"),
+];
+
+/// What `rust`, a type from src/webview/bindings.rs, looks like once it's
+/// been through JSON.
+fn typescript(rust: &str) -> String {
+    if let Some(inner) = rust.strip_prefix("Vec<").and_then(|t| t.strip_suffix('>')) {
+        let inner = typescript(inner);
+        return if inner.contains('|') { format!("({inner})[]") } else { format!("{inner}[]") };
+    }
+    if let Some(inner) = rust.strip_prefix("Option<").and_then(|t| t.strip_suffix('>')) {
+        return format!("{} | null", typescript(inner));
+    }
+
+    let ts = match rust {
+        "()" => "void",
+        "bool" => "boolean",
+        "String" => "string",
+        "u8" | "u16" | "u32" | "u64" | "usize" | "i32" | "i64" | "f64" => "number",
+        _ => TYPESCRIPT_NAMES.iter()
+            .find(|(name, _)| *name == rust)
+            .map(|(_, ts)| *ts)
+            .unwrap_or_else(|| panic!("src/webview/bindings.rs uses {rust}, which build.rs \
+                doesn't have a TypeScript name for.")),
+    };
+
+    String::from(ts)
 }
 
 fn minify(from: &str, to: &str) {
@@ -20,4 +124,4 @@ fn minify(from: &str, to: &str) {
     let inf = fs::read_to_string(from).unwrap();
     let mut outf = fs::File::create(to).unwrap();
     outf.write_all(&minify_html::minify(inf.as_bytes(), &minify_cfg)).unwrap();
-}
\ No newline at end of file
+}
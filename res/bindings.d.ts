@@ -0,0 +1,21 @@
+// Generated by build.rs from src/webview/bindings.rs.
+
+// settings.html
+declare function saveSettings(settings: object): Promise<{ field: string, message: string }[]>;
+declare function testNotifications(): Promise<void>;
+declare function loadSettings(): Promise<object>;
+declare function exportSettings(linksOnly: boolean): Promise<string>;
+declare function importSettings(mode: 'Merge' | 'Replace'): Promise<string>;
+declare function loadHistory(): Promise<any>;
+declare function revertTo(url: string, setAt: number): Promise<void>;
+declare function openPost(md5: string): Promise<void>;
+declare function loadFavorites(): Promise<any>;
+declare function removeFavorite(md5: string): Promise<void>;
+declare function applyFavorite(md5: string): Promise<void>;
+declare function loadMonitors(): Promise<object[]>;
+declare function loadThemes(): Promise<string[]>;
+declare function pickFolder(): Promise<string | null>;
+
+// background.html and themes
+declare function mediaFailed(url: string, reason: string): Promise<void>;
+declare function themeFailed(reason: string): Promise<void>;
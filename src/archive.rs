@@ -0,0 +1,216 @@
+//! Optionally keeping a copy of every post we're sent in a folder of the
+//! user's choosing.
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+
+use crate::{cache::MediaCache, state::Wallpaper};
+
+/// Remembers what's already been archived, so a post someone sets twice is
+/// only saved once even if the template doesn't include the md5.
+const INDEX: &str = ".walltaker-engine-archive.json";
+/// Saves run on blocking threads, and two close together would otherwise
+/// both read the index before either writes it back.
+static SAVING: Mutex<()> = Mutex::new(());
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub enabled: bool,
+    pub folder: String,
+    /// File name for each post. See [`file_name`] for the placeholders.
+    pub template: String,
+    /// Put each link's posts in their own subfolder.
+    pub per_link_folders: bool,
+    /// Write the update's details next to each post as JSON, in
+    /// `<post>.<ext>.json`.
+    pub sidecar: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            enabled: false,
+            folder: String::new(),
+            template: String::from("{date}_{link}_{setter}_{md5}.{ext}"),
+            per_link_folders: false,
+            sidecar: false,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Sidecar<'a> {
+    #[serde(flatten)]
+    wallpaper: &'a Wallpaper,
+    md5: &'a str,
+}
+
+/// Copy the cached post at `cached` into the archive. Returns where it went,
+/// or `None` if it was already there.
+pub fn save(settings: &Settings, wallpaper: &Wallpaper, cached: &Path) -> Result<Option<PathBuf>> {
+    let _saving = SAVING.lock().unwrap_or_else(PoisonError::into_inner);
+    let md5 = &MediaCache::md5_of(&wallpaper.url).context("Post has no md5")?;
+    let ext = cached.extension().map_or_else(String::new, |e| e.to_string_lossy().into_owned());
+
+    let root = PathBuf::from(&settings.folder);
+    let folder = if settings.per_link_folders {
+        root.join(wallpaper.link.to_string())
+    } else {
+        root.clone()
+    };
+    std::fs::create_dir_all(&folder)?;
+
+    let index_path = root.join(INDEX);
+    let mut index: HashSet<String> = File::open(&index_path).ok()
+        .and_then(|file| serde_json::from_reader(file).ok())
+        .unwrap_or_default();
+    if index.contains(md5) {
+        log::debug!("{md5} is already archived");
+        return Ok(None);
+    }
+
+    let (path, mut file) = create_unique(&folder.join(file_name(&settings.template, wallpaper, md5, &ext)))?;
+    std::io::copy(&mut File::open(cached)?, &mut file)?;
+
+    if settings.sidecar {
+        let file = File::create(sidecar_path(&path))?;
+        serde_json::to_writer_pretty(file, &Sidecar { wallpaper, md5 })?;
+    }
+
+    index.insert(String::from(md5));
+    serde_json::to_writer(File::create(&index_path)?, &index)?;
+
+    Ok(Some(path))
+}
+
+/// Next to the post with its whole name, since posts can share a stem.
+fn sidecar_path(post: &Path) -> PathBuf {
+    let mut name = post.file_name().unwrap_or_default().to_os_string();
+    name.push(".json");
+    post.with_file_name(name)
+}
+
+/// Create `path`, or `name (2).ext` and so on next to it if it's taken, so
+/// templates without `{md5}` don't overwrite older posts.
+fn create_unique(path: &Path) -> Result<(PathBuf, File)> {
+    let stem = path.file_stem().map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+    let ext = path.extension().map(|e| e.to_string_lossy().into_owned());
+
+    for n in 1.. {
+        let candidate = match (n, &ext) {
+            (1, _) => path.to_path_buf(),
+            (n, Some(ext)) => path.with_file_name(format!("{stem} ({n}).{ext}")),
+            (n, None) => path.with_file_name(format!("{stem} ({n})")),
+        };
+
+        match File::options().write(true).create_new(true).open(&candidate) {
+            Ok(file) => return Ok((candidate, file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => { },
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    unreachable!()
+}
+
+/// Fill in `template`. Knows `{date}`, `{link}`, `{setter}`, `{md5}` and
+/// `{ext}`.
+fn file_name(template: &str, wallpaper: &Wallpaper, md5: &str, ext: &str) -> String {
+    let name = template
+        .replace("{date}", &format_date(wallpaper.set_at))
+        .replace("{link}", &wallpaper.link.to_string())
+        .replace("{setter}", wallpaper.set_by.as_deref().unwrap_or("Anonymous"))
+        .replace("{md5}", md5)
+        .replace("{ext}", ext);
+
+    // Setters can call themselves whatever they like
+    name.chars()
+        .map(|c| if c.is_control() || "<>:\"/\\|?*".contains(c) { '_' } else { c })
+        .collect()
+}
+
+/// `YYYY-MM-DD_HH-MM-SS` (UTC) for a Unix timestamp. Days to date is Howard
+/// Hinnant's `civil_from_days`.
+#[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+fn format_date(secs: u64) -> String {
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+    let (hour, minute, second) = (secs / 3600, secs % 3600 / 60, secs % 60);
+
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}_{hour:02}-{minute:02}-{second:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    fn wallpaper(set_by: Option<&str>) -> Wallpaper {
+        Wallpaper {
+            url: String::from("https://static1.e621.net/data/01/23/0123456789abcdef0123456789abcdef.png"),
+            link: 4870,
+            set_by: set_by.map(String::from),
+            set_at: 1_700_000_000,
+            post: None,
+        }
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(format_date(0), "1970-01-01_00-00-00");
+        assert_eq!(format_date(951_782_400), "2000-02-29_00-00-00");
+        assert_eq!(format_date(1_700_000_000), "2023-11-14_22-13-20");
+        assert_eq!(format_date(4_102_444_799), "2099-12-31_23-59-59");
+    }
+
+    #[test]
+    fn templates() {
+        let name = file_name(&Settings::default().template, &wallpaper(Some("someone")), "abc", "png");
+        assert_eq!(name, "2023-11-14_22-13-20_4870_someone_abc.png");
+
+        let name = file_name("{setter}-{link}.{ext}", &wallpaper(None), "abc", "webm");
+        assert_eq!(name, "Anonymous-4870.webm");
+    }
+
+    #[test]
+    fn setters_cant_escape_the_folder() {
+        let name = file_name("{setter}.{ext}", &wallpaper(Some("../..\\x:y*\n")), "abc", "png");
+        assert_eq!(name, ".._.._x_y__.png");
+    }
+
+    #[test]
+    fn sidecars_dont_collide() {
+        let (png, webm) = (sidecar_path(Path::new("a/4870.png")), sidecar_path(Path::new("a/4870.webm")));
+        assert_eq!(png, Path::new("a/4870.png.json"));
+        assert_eq!(webm, Path::new("a/4870.webm.json"));
+    }
+
+    #[test]
+    fn existing_files_are_kept() {
+        let folder = TempDir::new("archive");
+        let path = folder.join("4870.png");
+        std::fs::write(&path, "old").unwrap();
+
+        let (second, _) = create_unique(&path).unwrap();
+        let (third, _) = create_unique(&path).unwrap();
+        assert_eq!(second, folder.join("4870 (2).png"));
+        assert_eq!(third, folder.join("4870 (3).png"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old");
+    }
+}
@@ -0,0 +1,307 @@
+//! Talking to the wallpaper page. Everything goes over as a serialized
+//! [`Command`] and `background.html` dispatches on the `command` field, so
+//! nothing we're sent by Walltaker or read from the config ever ends up being
+//! run as script.
+use std::{path::{Path, PathBuf}, sync::mpsc};
+use serde::{Serialize, Deserialize};
+use windows::Win32::Foundation::HWND;
+
+use crate::{
+    cache, collage, hwnd::{self, Monitor}, media::MediaKind, overlay, state::Wallpaper,
+    webview::{self, bindings, WebView}, FitMode, Letterbox, LoopPolicy, Transition,
+};
+
+const BACKGROUND_HTML: &str = include_str!(concat!(env!("OUT_DIR"), "/background.html.min"));
+/// Where a theme's folder is served from. See `res/themes.md`.
+pub const THEME_HOST: &str = "theme.walltaker-engine";
+
+/// Reports anything a theme throws, so it can be swapped for the built-in
+/// page. Init scripts stay around after that, hence the host check.
+fn theme_watchdog() -> String {
+    format!("
+        if (location.hostname === '{THEME_HOST}') {{
+            window.addEventListener('error', event => window.themeFailed(String(event.message)));
+            window.addEventListener('unhandledrejection', event => window.themeFailed(String(event.reason)));
+        }}
+    ")
+}
+
+/// Post-processing for whatever's up, mostly so desktop icons stay readable.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Adjustments {
+    /// Percent; 100 leaves it alone.
+    pub brightness: u8,
+    pub blur_px: u8,
+    pub tint: String,
+    /// How much `tint` covers it, in percent. 0 is no tint.
+    pub tint_strength: u8,
+    /// Percent.
+    pub grayscale: u8,
+}
+
+impl Default for Adjustments {
+    fn default() -> Self {
+        Adjustments {
+            brightness: 100,
+            blur_px: 0,
+            tint: String::from("#000000"),
+            tint_strength: 0,
+            grayscale: 0,
+        }
+    }
+}
+
+/// Variants are named for what they set; the page sees them as `set_*`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "command")]
+pub enum Command<'a> {
+    /// An `Unsupported` kind shows a placeholder instead.
+    #[serde(rename = "set_media")]
+    Media { url: &'a str, kind: MediaKind },
+    /// `auto_threshold` is only used by `FitMode::Auto`.
+    #[serde(rename = "set_fit")]
+    Fit { mode: &'a FitMode, auto_threshold: u8 },
+    #[serde(rename = "set_colour")]
+    Colour { colour: &'a str },
+    #[serde(rename = "set_letterbox")]
+    Letterbox { mode: &'a Letterbox },
+    #[serde(rename = "set_adjustments")]
+    Adjustments { adjustments: &'a Adjustments },
+    /// `enabled` is already worked out for this monitor.
+    #[serde(rename = "set_overlay")]
+    Overlay { enabled: bool, settings: &'a overlay::Settings },
+    /// What the overlay says.
+    #[serde(rename = "set_info")]
+    Info { wallpaper: &'a Wallpaper },
+    /// How long the page gives new media to load before giving up on it, and
+    /// whether it shows a spinner meanwhile.
+    #[serde(rename = "set_loading")]
+    Loading { indicator: bool, timeout_ms: u32 },
+    #[serde(rename = "set_transition")]
+    Transition { kind: &'a Transition, duration_ms: u32 },
+    /// `volume` is a percentage. Muting is done on the webview itself.
+    #[serde(rename = "set_playback")]
+    Playback { volume: u8, speed: f64, loop_policy: &'a LoopPolicy },
+    /// Lay the page out as a grid, the current post taking up the top left
+    /// and `tiles` filling the rest.
+    #[serde(rename = "set_collage")]
+    Collage {
+        enabled: bool,
+        columns: u8,
+        rows: u8,
+        fit: &'a FitMode,
+        tiles: &'a [collage::Tile],
+    },
+}
+
+/// Things the page tells us about.
+#[derive(Debug)]
+pub enum Event {
+    /// New media couldn't be loaded on the screen for `monitor`, so the old
+    /// wallpaper was left up.
+    LoadFailed { monitor: String, url: String, reason: String },
+    /// A custom theme couldn't be loaded or threw, on the screen for
+    /// `monitor`.
+    ThemeFailed { monitor: String, reason: String },
+}
+
+/// Has to be done before the page is navigated to.
+fn bind_events(view: &WebView, monitor: &str, tx: &mpsc::Sender<Event>) -> webview::Result<()> {
+    let (tx_, monitor_) = (tx.clone(), String::from(monitor));
+    view.bind_typed("mediaFailed", move |(url, reason): (String, String)| {
+        _ = tx_.send(Event::LoadFailed { monitor: monitor_.clone(), url, reason });
+        Ok(())
+    })?;
+
+    // Only ever called by the theme watchdog
+    let (tx_, monitor_) = (tx.clone(), String::from(monitor));
+    view.bind_typed("themeFailed", move |reason: String| {
+        _ = tx_.send(Event::ThemeFailed { monitor: monitor_.clone(), reason });
+        Ok(())
+    })?;
+
+    // build.rs checks the page against the declared bindings, so make sure
+    // they're what's actually bound
+    view.check_bound(bindings::BACKGROUND)?;
+
+    Ok(())
+}
+
+/// Also has to be done before the page is navigated to. The theme is left
+/// loaded even if it fails; see [`Screen::fall_back`].
+fn load_theme(view: &WebView, folder: &Path) -> webview::Result<()> {
+    view.init(&theme_watchdog())?;
+    view.map_folder(THEME_HOST, folder)?;
+    view.navigate(&format!("https://{THEME_HOST}/index.html"))?;
+
+    Ok(())
+}
+
+/// The wallpaper page on one monitor.
+pub struct Screen {
+    pub monitor: Monitor,
+    /// The theme folder asked for, even if it's since been swapped for the
+    /// built-in page.
+    pub theme: Option<PathBuf>,
+    themed: bool,
+    window: HWND,
+    pub view: WebView,
+}
+
+impl Screen {
+    /// `media` is the cache folder the page loads posts from. `theme` is a
+    /// folder with an `index.html` to use instead of the built-in page; if it
+    /// can't be loaded the built-in page is used and an
+    /// [`Event::ThemeFailed`] sent.
+    pub fn create(
+        workerw: HWND,
+        monitor: Monitor,
+        media: &Path,
+        theme: Option<&Path>,
+        events: &mpsc::Sender<Event>
+    ) -> webview::Result<Screen> {
+        let window = unsafe { hwnd::create_window(workerw, &monitor) };
+        // Which one gets sound is sorted out with the rest of the display
+        // settings
+        let view = WebView::create(Some(window), false, (100, 100))?;
+        view.map_folder(cache::HOST, media)?;
+        bind_events(&view, &monitor.id, events)?;
+
+        let themed = theme.is_some_and(|folder| match load_theme(&view, folder) {
+            Ok(()) => true,
+            Err(e) => {
+                _ = events.send(Event::ThemeFailed { monitor: monitor.id.clone(), reason: e.to_string() });
+                false
+            },
+        });
+        if !themed {
+            view.navigate_html(BACKGROUND_HTML)?;
+        }
+
+        Ok(Screen { monitor, theme: theme.map(Path::to_path_buf), themed, window, view })
+    }
+
+    /// Swap a broken theme for the built-in page. Returns whether anything
+    /// changed, in which case the page needs setting up again.
+    pub fn fall_back(&mut self) -> webview::Result<bool> {
+        if !self.themed {
+            return Ok(false);
+        }
+
+        self.themed = false;
+        self.view.navigate_html(BACKGROUND_HTML)?;
+        Ok(true)
+    }
+
+    pub fn close(self) {
+        if let Err(e) = self.view.close() {
+            log::warn!("Couldn't close the webview on {}: {e}", self.monitor.id);
+        }
+        unsafe { hwnd::destroy_window(self.window) };
+    }
+}
+
+pub fn send(view: &WebView, command: &Command) -> webview::Result<()> {
+    log::debug!("Background command: {command:?}");
+    view.post_message(command)?;
+
+    Ok(())
+}
+
+pub fn send_all(screens: &[Screen], command: &Command) -> webview::Result<()> {
+    for screen in screens {
+        send(&screen.view, command)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use super::*;
+
+    const HOSTILE: &[&str] = &[
+        "'); alert(1); ('",
+        "\"); alert(1); (\"",
+        "</script><script>alert(1)</script>",
+        "a\u{2028}alert(1)\u{2029}b",
+        "javascript:alert(1)",
+        "\\'\\\"`${alert(1)}`",
+    ];
+
+    /// What the page gets as `event.data`.
+    fn sent(command: &Command) -> Value {
+        let json = serde_json::to_string(command).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn media_urls_stay_data() {
+        for &url in HOSTILE {
+            assert_eq!(sent(&Command::Media { url, kind: MediaKind::Image }),
+                json!({ "command": "set_media", "url": url, "kind": "image" }));
+        }
+    }
+
+    #[test]
+    fn colours_stay_data() {
+        for &colour in HOSTILE {
+            assert_eq!(sent(&Command::Colour { colour }),
+                json!({ "command": "set_colour", "colour": colour }));
+        }
+    }
+
+    #[test]
+    fn info_stays_data() {
+        for &text in HOSTILE {
+            let wallpaper = Wallpaper {
+                url: String::from(text),
+                link: 1,
+                set_by: Some(String::from(text)),
+                set_at: 0,
+                post: Some(crate::e621::Post {
+                    artists: vec![String::from(text)],
+                    tags: vec![String::from(text)],
+                }),
+            };
+
+            assert_eq!(sent(&Command::Info { wallpaper: &wallpaper }), json!({
+                "command": "set_info",
+                "wallpaper": {
+                    "url": text,
+                    "link": 1,
+                    "set_by": text,
+                    "set_at": 0,
+                    "post": { "artists": [text], "tags": [text] },
+                },
+            }));
+        }
+    }
+
+    /// The built-in page is what broken themes fall back to, so it can't be
+    /// reported as broken itself. Init scripts outlive navigation, so that's
+    /// down to the watchdog only wiring anything up on the theme host.
+    #[test]
+    fn only_themes_report_theme_failures() {
+        let page = include_str!("../res/background.html");
+        assert!(!page.contains("themeFailed"));
+
+        let watchdog = theme_watchdog();
+        let (guard, wiring) = watchdog.split_once('{').unwrap();
+        assert_eq!(guard.trim(), format!("if (location.hostname === '{THEME_HOST}')"));
+        assert!(wiring.contains("themeFailed"));
+        assert!(!guard.contains("themeFailed"));
+    }
+
+    /// The other half: the page only ever puts what it's sent into DOM
+    /// properties and text.
+    #[test]
+    fn page_has_no_script_sinks() {
+        let page = include_str!("../res/background.html");
+        for sink in ["eval(", "Function(", "innerHTML", "outerHTML", "insertAdjacentHTML", "document.write"] {
+            assert!(!page.contains(sink), "background.html uses {sink}");
+        }
+    }
+}
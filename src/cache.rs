@@ -0,0 +1,348 @@
+//! Posts we've downloaded, kept on disk and shared by every background view
+//! so each one is fetched once rather than once per monitor per run. Files are
+//! named by the md5 e621 already puts in its URLs, and the least recently
+//! shown ones are thrown out once the cache grows past its cap.
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicU64, Ordering}, Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+use anyhow::{Context, Result};
+use md5::{Md5, Digest};
+
+use crate::media::{self, MediaKind};
+
+/// Background views see the cache directory under this host name.
+pub const HOST: &str = "media.walltaker-engine";
+/// How many times to try a download that comes back broken.
+const ATTEMPTS: u32 = 3;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a download can go without sending anything before it counts as
+/// failed. There's no overall limit since videos can be big.
+const STALL_TIMEOUT: Duration = Duration::from_secs(20);
+
+pub struct MediaCache {
+    dir: PathBuf,
+    max_bytes: AtomicU64,
+    client: reqwest::Client,
+    /// What's in `dir` by md5, so looking things up doesn't mean listing it.
+    index: Mutex<HashMap<String, PathBuf>>,
+    /// md5s of what's up on screen, which eviction leaves alone.
+    in_use: Mutex<HashSet<String>>,
+}
+
+impl MediaCache {
+    pub fn new(dir: PathBuf, max_mb: u64) -> Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+
+        let mut index = HashMap::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if is_partial(&path) {
+                // Left over from a download that got cut off
+                _ = std::fs::remove_file(&path);
+            } else if let Some(md5) = path.file_stem().and_then(|s| s.to_str()) {
+                index.insert(String::from(md5), path.clone());
+            }
+        }
+
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("WalltakerEngine/", env!("CARGO_PKG_VERSION")))
+            .connect_timeout(CONNECT_TIMEOUT)
+            .build()?;
+
+        Ok(MediaCache {
+            dir,
+            max_bytes: AtomicU64::new(max_mb * 1024 * 1024),
+            client,
+            index: Mutex::new(index),
+            in_use: Mutex::new(HashSet::new()),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn set_max_mb(&self, max_mb: u64) {
+        self.max_bytes.store(max_mb * 1024 * 1024, Ordering::Relaxed);
+    }
+
+    /// Mark `urls` as what's up on screen, so they're never evicted.
+    pub fn set_in_use<'a>(&self, urls: impl IntoIterator<Item = &'a str>) {
+        *self.in_use.lock().unwrap_or_else(PoisonError::into_inner) =
+            urls.into_iter().filter_map(Self::md5_of).collect();
+    }
+
+    fn index(&self) -> std::sync::MutexGuard<'_, HashMap<String, PathBuf>> {
+        self.index.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The md5 an e621 file URL is named by.
+    pub fn md5_of(url: &str) -> Option<String> {
+        let name = url.split(['?', '#']).next()?.rsplit('/').next()?;
+        let md5 = name.split_once('.').map_or(name, |(md5, _)| md5);
+
+        (md5.len() == 32 && md5.chars().all(|c| c.is_ascii_hexdigit()))
+            .then(|| md5.to_lowercase())
+    }
+
+    /// Where a background view can load a cached file from.
+    pub fn local_url(path: &Path) -> Option<String> {
+        let name = path.file_name()?.to_string_lossy();
+        Some(format!("https://{HOST}/{name}"))
+    }
+
+    /// Where `url` is in the cache, if it's there.
+    pub fn cached(&self, url: &str) -> Option<PathBuf> {
+        let md5 = Self::md5_of(url)?;
+
+        // Files are indexed by md5 alone, since ones with a missing or wrong
+        // extension are stored under whatever they turned out to be
+        let mut index = self.index();
+        let path = index.get(&md5)?.clone();
+        if !path.exists() {
+            // Deleted from outside
+            index.remove(&md5);
+            return None;
+        }
+        drop(index);
+
+        touch(&path);
+        Some(path)
+    }
+
+    /// Put a copy of a file we already trust (a favorite, say) back into the
+    /// cache as `url`.
+    pub fn insert(&self, url: &str, from: &Path) -> Result<PathBuf> {
+        if let Some(path) = self.cached(url) {
+            return Ok(path);
+        }
+
+        let md5 = Self::md5_of(url)
+            .ok_or_else(|| anyhow::anyhow!("Post URL isn't named by its md5"))?;
+        let ext = from.extension().map_or_else(|| String::from("bin"), |e| e.to_string_lossy().into_owned());
+        let path = self.dir.join(format!("{md5}.{ext}"));
+
+        std::fs::copy(from, &path)?;
+        self.index().insert(md5, path.clone());
+        if let Err(e) = self.evict(&path) {
+            log::warn!("Couldn't trim the media cache: {e}");
+        }
+
+        Ok(path)
+    }
+
+    /// Get `url` into the cache (if it isn't already) and return its path.
+    /// `None` means the URL isn't named by its md5, so there's nothing to
+    /// check it against and it can't be cached.
+    pub async fn fetch(&self, url: &str) -> Result<Option<PathBuf>> {
+        let Some(md5) = Self::md5_of(url) else {
+            return Ok(None);
+        };
+
+        if let Some(path) = self.cached(url) {
+            log::debug!("Cache hit for {url}");
+            return Ok(Some(path));
+        }
+
+        let mut attempt = 1;
+        let bytes = loop {
+            match self.download(url, &md5).await {
+                Ok(bytes) => break bytes,
+                Err(e) if attempt < ATTEMPTS => {
+                    log::warn!("Download {attempt}/{ATTEMPTS} of {url} failed, retrying: {e:#}");
+                    tokio::time::sleep(Duration::from_secs(u64::from(attempt))).await;
+                    attempt += 1;
+                },
+                Err(e) => return Err(e),
+            }
+        };
+
+        // Trust the URL's extension if it's one we know, otherwise name the
+        // file after what it looks like.
+        let ext = match media::extension(url) {
+            Some(ext) if media::kind_from_extension(&ext) != MediaKind::Unsupported => ext,
+            ext => media::sniff(&bytes).map(|(_, sniffed)| String::from(sniffed))
+                .or(ext)
+                .unwrap_or_else(|| String::from("bin")),
+        };
+        let path = self.dir.join(format!("{md5}.{ext}"));
+
+        // Written under another name first so a half-written file never
+        // looks cached.
+        let partial = path.with_extension("part");
+        tokio::fs::write(&partial, &bytes).await?;
+        tokio::fs::rename(&partial, &path).await?;
+        self.index().insert(md5, path.clone());
+
+        if let Err(e) = self.evict(&path) {
+            log::warn!("Couldn't trim the media cache: {e}");
+        }
+
+        Ok(Some(path))
+    }
+
+    /// Download `url` and make sure it's actually the file called `md5`.
+    /// Truncated and corrupted downloads are errors.
+    async fn download(&self, url: &str, md5: &str) -> Result<Vec<u8>> {
+        log::debug!("Downloading {url}");
+        // reqwest 0.11 has no read timeout, so watch for stalls ourselves
+        let mut response = tokio::time::timeout(STALL_TIMEOUT, self.client.get(url).send()).await
+            .context("The server didn't answer")??
+            .error_for_status()?;
+        let mut bytes = Vec::new();
+        while let Some(chunk) = tokio::time::timeout(STALL_TIMEOUT, response.chunk()).await
+            .context("The download stalled")??
+        {
+            bytes.extend_from_slice(&chunk);
+        }
+
+        let actual = format!("{:x}", Md5::digest(&bytes));
+        if actual != md5 {
+            log::error!("md5 mismatch for {url}: got {actual} from {} bytes", bytes.len());
+            anyhow::bail!("Downloaded file doesn't match its md5 (got {actual})");
+        }
+
+        Ok(bytes)
+    }
+
+    /// Delete the least recently used files until we're under the cap. `keep`
+    /// is never deleted; it's what we're about to show. Neither is anything
+    /// on screen or still downloading.
+    fn evict(&self, keep: &Path) -> Result<()> {
+        let max = self.max_bytes.load(Ordering::Relaxed);
+        let in_use = self.in_use.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let mut total = 0;
+        let mut files = Vec::new();
+
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            let path = entry.path();
+            if meta.is_file() && !is_partial(&path) {
+                total += meta.len();
+                files.push((meta.modified()?, meta.len(), path));
+            }
+        }
+
+        files.sort_by_key(|(used, ..)| *used);
+        for (_, len, path) in files {
+            if total <= max {
+                break;
+            }
+            let md5 = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+            if path == keep || in_use.contains(md5) {
+                continue;
+            }
+
+            log::debug!("Evicting {}", path.display());
+            std::fs::remove_file(&path)?;
+            self.index().remove(md5);
+            total -= len;
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `path` is a download that's still being written.
+fn is_partial(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "part")
+}
+
+/// Bump a file's modified time, which is what eviction goes by.
+fn touch(path: &Path) {
+    if let Ok(file) = File::options().append(true).open(path) {
+        _ = file.set_modified(SystemTime::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    const MD5: &str = "0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn md5_from_e621_urls() {
+        for url in [
+            format!("https://static1.e621.net/data/01/23/{MD5}.png"),
+            format!("https://static1.e621.net/data/01/23/{MD5}.webm?download=1"),
+            format!("https://static1.e621.net/data/01/23/{MD5}.jpg#top"),
+            format!("https://static1.e621.net/data/01/23/{MD5}"),
+            format!("https://static1.e621.net/data/01/23/{}.png", MD5.to_uppercase()),
+        ] {
+            assert_eq!(MediaCache::md5_of(&url).as_deref(), Some(MD5), "{url}");
+        }
+    }
+
+    #[test]
+    fn no_md5() {
+        for url in [
+            "",
+            "https://e621.net/",
+            "https://e621.net/posts/12345",
+            "https://static1.e621.net/data/01/23/0123456789abcdef.png",
+            "https://static1.e621.net/data/01/23/0123456789abcdef0123456789abcdeg.png",
+            "https://static1.e621.net/data/01/23/../../0123456789abcdef0123456789abcdef0.png",
+            "https://example.com/x?file=/0123456789abcdef0123456789abcdef.png",
+        ] {
+            assert_eq!(MediaCache::md5_of(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn local_urls() {
+        let path = Path::new("cache").join(format!("{MD5}.png"));
+        assert_eq!(MediaCache::local_url(&path), Some(format!("https://{HOST}/{MD5}.png")));
+    }
+
+    fn url(md5: &str, ext: &str) -> String {
+        format!("https://static1.e621.net/data/01/23/{md5}.{ext}")
+    }
+
+    #[test]
+    fn finds_files_whatever_their_extension() {
+        let dir = TempDir::new("cache-index");
+        std::fs::write(dir.join(&format!("{MD5}.webm")), "video").unwrap();
+
+        let cache = MediaCache::new(dir.path().to_path_buf(), 100).unwrap();
+        assert_eq!(cache.cached(&url(MD5, "png")), Some(dir.join(&format!("{MD5}.webm"))));
+        assert_eq!(cache.cached(&url(&"f".repeat(32), "png")), None);
+
+        std::fs::remove_file(dir.join(&format!("{MD5}.webm"))).unwrap();
+        assert_eq!(cache.cached(&url(MD5, "png")), None);
+    }
+
+    #[test]
+    fn leftover_downloads_are_cleared() {
+        let dir = TempDir::new("cache-leftovers");
+        std::fs::write(dir.join(&format!("{MD5}.part")), "half").unwrap();
+
+        let cache = MediaCache::new(dir.path().to_path_buf(), 100).unwrap();
+        assert!(!dir.join(&format!("{MD5}.part")).exists());
+        assert_eq!(cache.cached(&url(MD5, "png")), None);
+    }
+
+    #[test]
+    fn eviction_spares_whats_in_use() {
+        let dir = TempDir::new("cache-evict");
+        let cache = MediaCache::new(dir.path().to_path_buf(), 0).unwrap();
+        let [shown, kept, old, downloading] = ["a", "b", "c", "d"].map(|c| c.repeat(32));
+        for (md5, ext) in [(&shown, "png"), (&kept, "png"), (&old, "png"), (&downloading, "part")] {
+            std::fs::write(dir.join(&format!("{md5}.{ext}")), "x").unwrap();
+        }
+
+        cache.set_in_use([url(&shown, "png").as_str()]);
+        cache.evict(&dir.join(&format!("{kept}.png"))).unwrap();
+
+        assert!(dir.join(&format!("{shown}.png")).exists());
+        assert!(dir.join(&format!("{kept}.png")).exists());
+        assert!(dir.join(&format!("{downloading}.part")).exists());
+        assert!(!dir.join(&format!("{old}.png")).exists());
+    }
+}
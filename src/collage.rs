@@ -0,0 +1,47 @@
+//! Showing the current post big with the ones before it tiled around it.
+use serde::{Serialize, Deserialize};
+
+use crate::{cache::MediaCache, history::History, media::MediaKind, FitMode};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub enabled: bool,
+    pub columns: u8,
+    pub rows: u8,
+    /// How the tiles fit their cells. The current post uses the usual fit.
+    pub tile_fit: FitMode,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            enabled: false,
+            columns: 4,
+            rows: 3,
+            tile_fit: FitMode::Fill,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Tile {
+    pub url: String,
+    pub kind: MediaKind,
+}
+
+/// The most recent posts other than `current`, enough to fill the grid.
+pub fn tiles(settings: &Settings, history: &History, cache: &MediaCache, current: Option<&str>) -> Vec<Tile> {
+    let cells = usize::from(settings.columns) * usize::from(settings.rows);
+
+    history.entries().iter()
+        .filter(|entry| Some(entry.url.as_str()) != current && entry.kind != MediaKind::Unsupported)
+        .take(cells)
+        .map(|entry| Tile {
+            url: cache.cached(&entry.url)
+                .and_then(|path| MediaCache::local_url(&path))
+                .unwrap_or_else(|| entry.url.clone()),
+            kind: entry.kind,
+        })
+        .collect()
+}
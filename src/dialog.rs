@@ -0,0 +1,75 @@
+//! Standard Windows file and folder pickers.
+use std::path::PathBuf;
+use windows::{
+    core::{w, PWSTR},
+    Win32::{
+        Foundation::HWND,
+        System::Com::CoTaskMemFree,
+        UI::{Controls::Dialogs, Shell},
+    },
+};
+
+/// Show the standard Windows open/save dialog. `None` if the user cancels.
+pub fn pick_file(owner: HWND, save: bool) -> Option<PathBuf> {
+    let mut buffer = [0u16; 260];
+    let mut ofn = Dialogs::OPENFILENAMEW {
+        #[allow(clippy::cast_possible_truncation)]
+        lStructSize: std::mem::size_of::<Dialogs::OPENFILENAMEW>() as u32,
+        hwndOwner: owner,
+        lpstrFilter: w!("Walltaker Engine settings (*.json)\0*.json\0All files\0*.*\0"),
+        lpstrFile: PWSTR(buffer.as_mut_ptr()),
+        #[allow(clippy::cast_possible_truncation)]
+        nMaxFile: buffer.len() as u32,
+        lpstrDefExt: w!("json"),
+        Flags: if save {
+            Dialogs::OFN_OVERWRITEPROMPT | Dialogs::OFN_NOCHANGEDIR
+        } else {
+            Dialogs::OFN_FILEMUSTEXIST | Dialogs::OFN_PATHMUSTEXIST | Dialogs::OFN_NOCHANGEDIR
+        },
+        ..Default::default()
+    };
+
+    let picked = unsafe {
+        if save {
+            Dialogs::GetSaveFileNameW(&mut ofn)
+        } else {
+            Dialogs::GetOpenFileNameW(&mut ofn)
+        }
+    };
+    if !picked.as_bool() {
+        return None;
+    }
+
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    Some(PathBuf::from(String::from_utf16_lossy(&buffer[..len])))
+}
+
+/// Show the folder picker. `None` if the user cancels.
+pub fn pick_folder(owner: HWND) -> Option<PathBuf> {
+    let mut display_name = [0u16; 260];
+    let info = Shell::BROWSEINFOW {
+        hwndOwner: owner,
+        pszDisplayName: PWSTR(display_name.as_mut_ptr()),
+        lpszTitle: w!("Where should wallpapers be saved?"),
+        ulFlags: Shell::BIF_RETURNONLYFSDIRS | Shell::BIF_NEWDIALOGSTYLE,
+        ..Default::default()
+    };
+
+    let mut path = [0u16; 260];
+    let picked = unsafe {
+        let list = Shell::SHBrowseForFolderW(&info);
+        if list.is_null() {
+            return None;
+        }
+
+        let picked = Shell::SHGetPathFromIDListW(list, &mut path);
+        CoTaskMemFree(Some(list as *const _));
+        picked
+    };
+    if !picked.as_bool() {
+        return None;
+    }
+
+    let len = path.iter().position(|&c| c == 0).unwrap_or(path.len());
+    Some(PathBuf::from(String::from_utf16_lossy(&path[..len])))
+}
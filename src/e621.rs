@@ -0,0 +1,66 @@
+//! Looking posts up on e621 for the details Walltaker doesn't send us.
+use std::{sync::OnceLock, time::Duration};
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+
+const API: &str = "https://e621.net/posts.json";
+/// How many general tags are worth showing.
+const MAX_TAGS: usize = 12;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Post {
+    pub artists: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Posts {
+    posts: Vec<ApiPost>,
+}
+
+#[derive(Deserialize)]
+struct ApiPost {
+    tags: ApiTags,
+}
+
+#[derive(Deserialize)]
+struct ApiTags {
+    #[serde(default)]
+    artist: Vec<String>,
+    #[serde(default)]
+    general: Vec<String>,
+}
+
+fn client() -> Result<&'static reqwest::Client> {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    if let Some(client) = CLIENT.get() {
+        return Ok(client);
+    }
+
+    // e621 turns away requests without a descriptive user agent
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("WalltakerEngine/", env!("CARGO_PKG_VERSION"), " (by dogkisser)"))
+        .timeout(Duration::from_secs(10))
+        .build()?;
+
+    Ok(CLIENT.get_or_init(|| client))
+}
+
+/// The post with this md5, or `None` if e621 doesn't have it (any more).
+pub async fn lookup(md5: &str) -> Result<Option<Post>> {
+    let body = client()?
+        .get(API)
+        .query(&[("tags", format!("md5:{md5}"))])
+        .send().await?
+        .error_for_status()?
+        .bytes().await?;
+    let posts: Posts = serde_json::from_slice(&body)?;
+
+    Ok(posts.posts.into_iter().next().map(|post| Post {
+        // Not actually artists, but e621 files them there
+        artists: post.tags.artist.into_iter()
+            .filter(|a| !matches!(a.as_str(), "conditional_dnp" | "sound_warning" | "unknown_artist"))
+            .collect(),
+        tags: post.tags.general.into_iter().take(MAX_TAGS).collect(),
+    }))
+}
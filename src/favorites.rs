@@ -0,0 +1,93 @@
+//! Posts the user's chosen to keep. Unlike the cache these are never thrown
+//! out, so they live in their own folder with an index of their details.
+use std::{fs::File, path::{Path, PathBuf}};
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+
+use crate::{cache::MediaCache, media::MediaKind, history, state::{self, Wallpaper}};
+
+/// The settings window sees the favorites folder under this host name.
+pub const HOST: &str = "favorites.walltaker-engine";
+const INDEX: &str = "favorites.json";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Favorite {
+    #[serde(flatten)]
+    pub entry: history::Entry,
+    /// Name of the copy in the favorites folder.
+    pub file: String,
+    /// Seconds since the Unix epoch.
+    pub favorited_at: u64,
+}
+
+pub struct Favorites {
+    dir: PathBuf,
+    entries: Vec<Favorite>,
+}
+
+impl Favorites {
+    pub fn load(dir: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        let entries = File::open(dir.join(INDEX)).ok()
+            .and_then(|file| serde_json::from_reader(file)
+                .map_err(|e| log::warn!("Couldn't read favorites: {e}"))
+                .ok())
+            .unwrap_or_default();
+
+        Ok(Favorites { dir, entries })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn entries(&self) -> &[Favorite] {
+        &self.entries
+    }
+
+    pub fn get(&self, md5: &str) -> Option<&Favorite> {
+        self.entries.iter().find(|f| f.entry.md5.as_deref() == Some(md5))
+    }
+
+    pub fn path_of(&self, favorite: &Favorite) -> PathBuf {
+        self.dir.join(&favorite.file)
+    }
+
+    /// Copy `cached` into favorites. `false` if it was already a favorite.
+    pub fn add(&mut self, wallpaper: &Wallpaper, kind: MediaKind, cached: &Path) -> Result<bool> {
+        let md5 = MediaCache::md5_of(&wallpaper.url).context("Post has no md5")?;
+        if self.get(&md5).is_some() {
+            return Ok(false);
+        }
+
+        let file = cached.file_name().context("Cached file has no name")?;
+        std::fs::copy(cached, self.dir.join(file))?;
+
+        self.entries.insert(0, Favorite {
+            entry: history::Entry::new(wallpaper, kind),
+            file: file.to_string_lossy().into_owned(),
+            favorited_at: state::now(),
+        });
+        self.save()?;
+
+        Ok(true)
+    }
+
+    pub fn remove(&mut self, md5: &str) -> Result<()> {
+        let Some(i) = self.entries.iter().position(|f| f.entry.md5.as_deref() == Some(md5)) else {
+            return Ok(());
+        };
+
+        let favorite = self.entries.remove(i);
+        _ = std::fs::remove_file(self.path_of(&favorite));
+
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        let file = File::create(self.dir.join(INDEX))?;
+        serde_json::to_writer(file, &self.entries)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+//! Every wallpaper we've been sent, newest first, so they can be browsed and
+//! put back up from the settings window.
+use std::{fs::File, path::PathBuf};
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+
+use crate::{cache::MediaCache, e621, media::MediaKind, state::Wallpaper};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub url: String,
+    pub md5: Option<String>,
+    pub link: usize,
+    pub set_by: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub set_at: u64,
+    pub kind: MediaKind,
+    /// Details from e621, if we looked them up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post: Option<e621::Post>,
+}
+
+impl Entry {
+    pub fn new(wallpaper: &Wallpaper, kind: MediaKind) -> Self {
+        Entry {
+            url: wallpaper.url.clone(),
+            md5: MediaCache::md5_of(&wallpaper.url),
+            link: wallpaper.link,
+            set_by: wallpaper.set_by.clone(),
+            set_at: wallpaper.set_at,
+            kind,
+            post: wallpaper.post.clone(),
+        }
+    }
+
+    pub fn wallpaper(&self) -> Wallpaper {
+        Wallpaper {
+            url: self.url.clone(),
+            link: self.link,
+            set_by: self.set_by.clone(),
+            set_at: self.set_at,
+            post: self.post.clone(),
+        }
+    }
+}
+
+pub struct History {
+    path: PathBuf,
+    entries: Vec<Entry>,
+}
+
+impl History {
+    pub fn load(path: PathBuf) -> Self {
+        let entries = File::open(&path).ok()
+            .and_then(|file| serde_json::from_reader(file)
+                .map_err(|e| log::warn!("Couldn't read wallpaper history: {e}"))
+                .ok())
+            .unwrap_or_default();
+
+        History { path, entries }
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Record `entry`, dropping the oldest entries past `limit`.
+    pub fn push(&mut self, entry: Entry, limit: usize) -> Result<()> {
+        self.entries.insert(0, entry);
+        self.entries.truncate(limit);
+
+        self.save()
+    }
+
+    /// Fill in the e621 details of `wallpaper`'s entry, if it has one.
+    pub fn update_post(&mut self, wallpaper: &Wallpaper) -> Result<()> {
+        let entry = self.entries.iter_mut()
+            .find(|e| e.url == wallpaper.url && e.set_at == wallpaper.set_at);
+        let Some(entry) = entry else { return Ok(()) };
+        entry.post.clone_from(&wallpaper.post);
+
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        let file = File::create(&self.path)?;
+        serde_json::to_writer(file, &self.entries)?;
+
+        Ok(())
+    }
+}
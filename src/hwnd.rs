@@ -1,21 +1,67 @@
 //! This module handles the hacky hack(s) required to get a video playing as
 //! the wallpaper.
+use std::sync::atomic::{AtomicBool, Ordering};
 use log::info;
+use serde::Serialize;
 #[allow(clippy::wildcard_imports)]
 use windows::{
-    core::{s, PCSTR},
+    core::{s, PCSTR, PCWSTR},
     Win32::{
         Foundation::{BOOL, HINSTANCE, HWND, LPARAM, LRESULT, RECT, WPARAM},
         UI::WindowsAndMessaging::*,
-        Graphics::Gdi::{HMONITOR, HDC, EnumDisplayMonitors, HBRUSH},
+        Graphics::Gdi::{
+            HMONITOR, HDC, EnumDisplayMonitors, HBRUSH, GetMonitorInfoW, MONITORINFO,
+            MONITORINFOEXW, EnumDisplayDevicesW, DISPLAY_DEVICEW,
+        },
         System::LibraryLoader::GetModuleHandleA,
     },
 };
 
-/// This function creates a window between the wallpaper and desktop icons
-/// for each monitor. Based on this:
+/// A connected monitor.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Monitor {
+    /// Survives reboots and the monitor being moved around in Display
+    /// Settings, unlike its position in the enumeration order.
+    pub id: String,
+    /// What Windows calls it, e.g. `DELL U2415`.
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Monitor {
+    /// One big pretend monitor covering all of `monitors`, for spanning a
+    /// wallpaper across them. Gaps between them (and anything off the edges
+    /// of differently sized ones) are just never seen.
+    pub fn span<'a>(monitors: impl IntoIterator<Item = &'a Monitor>) -> Option<Monitor> {
+        let (mut left, mut top, mut right, mut bottom) = (i32::MAX, i32::MAX, i32::MIN, i32::MIN);
+        for monitor in monitors {
+            left = left.min(monitor.x);
+            top = top.min(monitor.y);
+            right = right.max(monitor.x + monitor.width);
+            bottom = bottom.max(monitor.y + monitor.height);
+        }
+
+        (left < right).then(|| Monitor {
+            id: String::from(SPAN_ID),
+            name: String::from("All Monitors"),
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        })
+    }
+}
+
+/// [`Monitor::id`] of the spanning monitor.
+pub const SPAN_ID: &str = "span";
+
+/// This function finds (or creates) the window between the wallpaper and
+/// desktop icons that ours go into. Based on this:
 /// <https://www.codeproject.com/Articles/856020/Draw-Behind-Desktop-Icons-in-Windows-plus>
-pub unsafe fn find_hwnds() -> anyhow::Result<Vec<HWND>> {
+pub unsafe fn find_workerw() -> anyhow::Result<HWND> {
     let progman = FindWindowA(s!("Progman"), PCSTR::null());
     anyhow::ensure!(progman.0 != 0, "No progman process");
 
@@ -60,18 +106,93 @@ pub unsafe fn find_hwnds() -> anyhow::Result<Vec<HWND>> {
     };
     RegisterClassA(std::ptr::addr_of!(class));
 
-    // This pushes the WorkerW HWND as the first element of the Vec so I don't
-    // have to bother creating a struct etc. to move that extra information in.
-    let mut hwnds = Vec::from(&[workerw_hwnd]);
-    let ptr = std::ptr::addr_of_mut!(hwnds) as isize;
-    // Create that new window for each monitor
+    Ok(workerw_hwnd)
+}
+
+/// Every connected monitor, in no particular order.
+pub unsafe fn monitors() -> Vec<Monitor> {
+    let mut monitors = Vec::new();
+    let ptr = std::ptr::addr_of_mut!(monitors) as isize;
     EnumDisplayMonitors(HDC(0), None, Some(enum_monitors_proc), LPARAM(ptr));
-    // The WorkerW HWND is removed at the end :)
-    hwnds.swap_remove(0);
 
-    anyhow::ensure!(!hwnds.is_empty(), "Couldn't create HWNDs");
+    monitors
+}
+
+/// Make a window covering `monitor` for a wallpaper to go in.
+pub unsafe fn create_window(workerw_hwnd: HWND, monitor: &Monitor) -> HWND {
+    let Monitor { x, y, width, height, .. } = *monitor;
+    info!("Creating window for {} at {x}:{y} size {width}:{height}", monitor.name);
+
+    CreateWindowExA(
+        WS_EX_NOACTIVATE,
+        s!("Walltaker Engine"),
+        s!(""),
+        WS_CHILD | WS_VISIBLE,
+        x,
+        y,
+        width,
+        height,
+        workerw_hwnd,
+        HMENU(0),
+        HINSTANCE(0),
+        None,
+    )
+}
 
-    Ok(hwnds)
+pub unsafe fn destroy_window(hwnd: HWND) {
+    _ = DestroyWindow(hwnd);
+}
+
+/// Set when monitors are plugged in, unplugged or change resolution.
+static DISPLAY_CHANGED: AtomicBool = AtomicBool::new(false);
+
+/// Start listening for monitors changing. `WM_DISPLAYCHANGE` only goes to
+/// top-level windows, so ours (which live in `WorkerW`) never see it; this makes
+/// an invisible one that does. Its messages are pumped along with everything
+/// else on this thread.
+pub unsafe fn watch_displays() -> anyhow::Result<()> {
+    let class = WNDCLASSA {
+        lpfnWndProc: Some(display_watcher_proc),
+        hInstance: HINSTANCE(GetModuleHandleA(PCSTR::null())?.0),
+        lpszClassName: s!("Walltaker Engine Display Watcher"),
+        ..Default::default()
+    };
+    RegisterClassA(std::ptr::addr_of!(class));
+
+    let hwnd = CreateWindowExA(
+        WS_EX_NOACTIVATE,
+        s!("Walltaker Engine Display Watcher"),
+        s!(""),
+        WS_OVERLAPPED,
+        0, 0, 0, 0,
+        HWND(0),
+        HMENU(0),
+        HINSTANCE(0),
+        None,
+    );
+    anyhow::ensure!(hwnd.0 != 0, "Couldn't create the display watcher window");
+
+    Ok(())
+}
+
+/// Whether monitors have changed since this was last called.
+pub fn displays_changed() -> bool {
+    DISPLAY_CHANGED.swap(false, Ordering::Relaxed)
+}
+
+unsafe extern "system" fn display_watcher_proc(
+    hwnd: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM
+) -> LRESULT
+{
+    if message == WM_DISPLAYCHANGE {
+        info!("Displays changed");
+        DISPLAY_CHANGED.store(true, Ordering::Relaxed);
+    }
+
+    DefWindowProcA(hwnd, message, wparam, lparam)
 }
 
 // This function HAS to be defined (or the application hangs, interestingly)
@@ -105,39 +226,55 @@ unsafe extern "system" fn enum_windows_proc(hwnd: HWND, out: LPARAM) -> BOOL {
     true.into()
 }
 
-/// Called by ``EnumDisplayMonitors``; for every connected monitor, spawn a new
-/// window to fit it.
+/// Called by ``EnumDisplayMonitors``; collects the details of every connected
+/// monitor.
 unsafe extern "system" fn enum_monitors_proc(
-    _: HMONITOR,
+    hmonitor: HMONITOR,
     _: HDC,
     rect: *mut RECT,
     out: LPARAM,
 ) -> BOOL {
-    let hwnds: &mut Vec<HWND> = &mut *(out.0 as *mut _);
-    let workerw_hwnd = hwnds[0];
+    let monitors: &mut Vec<Monitor> = &mut *(out.0 as *mut _);
 
     let RECT { left: x, top: y, right, bottom } = *rect;
-    let width = right - x;
-    let height = bottom - y;
+    let (id, name) = identify(hmonitor)
+        .unwrap_or_else(|| (format!("{x},{y}"), format!("Monitor {}", monitors.len() + 1)));
 
-    info!("Creating window at {x}:{y} size {width}:{height}");
+    monitors.push(Monitor { id, name, x, y, width: right - x, height: bottom - y });
 
-    let next_hwnd = CreateWindowExA(
-        WS_EX_NOACTIVATE,
-        s!("Walltaker Engine"),
-        s!(""),
-        WS_CHILD | WS_VISIBLE,
-        x,
-        y,
-        width,
-        height,
-        workerw_hwnd,
-        HMENU(0),
-        HINSTANCE(0),
-        None,
-    );
+    true.into()
+}
 
-    hwnds.push(next_hwnd);
+/// The monitor's device interface path and friendly name. The adapter name
+/// (`\\.\DISPLAY1`) is a fallback for the id, but it can get shuffled when
+/// monitors are plugged in in a different order.
+unsafe fn identify(hmonitor: HMONITOR) -> Option<(String, String)> {
+    let mut info = MONITORINFOEXW::default();
+    info.monitorInfo.cbSize = u32::try_from(std::mem::size_of::<MONITORINFOEXW>()).ok()?;
+    if !GetMonitorInfoW(hmonitor, std::ptr::addr_of_mut!(info).cast::<MONITORINFO>()).as_bool() {
+        return None;
+    }
+    let adapter = from_wide(&info.szDevice);
 
-    true.into()
-}
\ No newline at end of file
+    let mut device = DISPLAY_DEVICEW {
+        cb: u32::try_from(std::mem::size_of::<DISPLAY_DEVICEW>()).ok()?,
+        ..Default::default()
+    };
+    let found = EnumDisplayDevicesW(
+        PCWSTR(info.szDevice.as_ptr()),
+        0,
+        &mut device,
+        EDD_GET_DEVICE_INTERFACE_NAME,
+    ).as_bool();
+
+    if found && device.DeviceID[0] != 0 {
+        Some((from_wide(&device.DeviceID), from_wide(&device.DeviceString)))
+    } else {
+        Some((adapter.clone(), adapter))
+    }
+}
+
+fn from_wide(wide: &[u16]) -> String {
+    let len = wide.iter().position(|&c| c == 0).unwrap_or(wide.len());
+    String::from_utf16_lossy(&wide[..len])
+}
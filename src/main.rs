@@ -11,9 +11,14 @@ use tokio_tungstenite::{WebSocketStream, MaybeTlsStream, tungstenite::Message};
 use tray_item::{IconSource, TrayItem};
 use rand::prelude::*;
 use tauri_winrt_notification::Toast;
+use background::{Command, Screen};
+use media::MediaKind;
+use cache::MediaCache;
 use std::{
+    collections::HashMap,
     fs::File,
     rc::Rc,
+    sync::Arc,
     path::{PathBuf, Path},
     time::Duration,
     task::Poll::Ready,
@@ -30,33 +35,148 @@ use simplelog::{
     WriteLogger, TerminalMode
 };
 
+mod archive;
+mod background;
+mod cache;
+mod collage;
+mod dialog;
+mod e621;
+mod favorites;
+mod history;
 mod hwnd;
+mod media;
+mod monitor;
+mod overlay;
+mod portable;
+mod profile;
+mod state;
+#[cfg(test)]
+mod temp_dir;
+mod validation;
 mod webview;
 mod walltaker;
 
 type Writer = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
 
+/// A post that's finished going through the cache.
+struct Fetched {
+    /// Order the fetch was started in.
+    seq: u64,
+    wallpaper: state::Wallpaper,
+    /// Whether this came from Walltaker, as opposed to being put back up from
+    /// history.
+    from_walltaker: bool,
+    cached: Result<Option<PathBuf>>,
+}
+
+/// What [`spawn_fetch`] sends back.
+enum Fetch {
+    Cached(Fetched),
+    /// The same wallpaper with its details from e621 filled in. Always comes
+    /// after its [`Fetch::Cached`].
+    LookedUp(state::Wallpaper),
+}
+
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
 #[serde(default)]
 #[allow(clippy::struct_excessive_bools)]
 struct Config {
     links: Vec<usize>,
     fit_mode: FitMode,
+    /// How far apart (in percent) a post's shape and the screen's can be
+    /// before `FitMode::Auto` stops cropping and fits instead.
+    #[serde(default = "default_auto_fit_threshold")]
+    auto_fit_threshold: u8,
     notifications: bool,
     background_colour: String,
+    letterbox: Letterbox,
+    adjustments: background::Adjustments,
     run_on_boot: bool,
     debug_logs: bool,
     vibrate_for: u16,
     vibration_intensity: u8,
+    #[serde(default = "default_cache_size_mb")]
+    cache_size_mb: u64,
+    #[serde(default = "default_history_limit")]
+    history_limit: usize,
+    loading_indicator: bool,
+    transition: Transition,
+    #[serde(default = "default_transition_ms")]
+    transition_ms: u32,
+    #[serde(default = "default_volume")]
+    volume: u8,
+    muted: bool,
+    #[serde(default = "default_playback_speed")]
+    playback_speed: f64,
+    loop_policy: LoopPolicy,
+    monitors: Vec<monitor::Settings>,
+    /// Stretch one wallpaper across every enabled monitor.
+    span: bool,
+    /// Where links no monitor's been given go. `None` is every monitor
+    /// without links of its own.
+    default_monitor: Option<String>,
+    #[serde(default = "default_load_timeout_secs")]
+    load_timeout_secs: u32,
+    collage: collage::Settings,
+    /// Folder name of a custom background page in the themes folder, next to
+    /// this config.
+    theme: Option<String>,
+    overlay: overlay::Settings,
+    archive: archive::Settings,
+    profiles: Vec<profile::Profile>,
+    active_profile: Option<String>,
     version: String,
 }
 
+fn default_cache_size_mb() -> u64 { 512 }
+fn default_history_limit() -> usize { 200 }
+fn default_auto_fit_threshold() -> u8 { 25 }
+fn default_load_timeout_secs() -> u32 { 30 }
+fn default_transition_ms() -> u32 { 500 }
+fn default_volume() -> u8 { 100 }
+fn default_playback_speed() -> f64 { 1. }
+
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
 enum FitMode {
     Stretch,
     #[default]
     Fit,
     Fill,
+    /// Fill if the post's about the screen's shape, otherwise Fit. Decided
+    /// per post by the background page.
+    Auto,
+}
+
+/// What fills the bars around a post that doesn't cover the screen.
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+enum Letterbox {
+    /// `background_colour`.
+    #[default]
+    Colour,
+    /// A blurred, darkened copy of the post.
+    Blur,
+    /// The post's most common colour.
+    Auto,
+}
+
+/// How a new wallpaper replaces the old one.
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+enum Transition {
+    /// A hard cut.
+    None,
+    #[default]
+    Crossfade,
+    Slide,
+    Zoom,
+}
+
+/// What videos do when they reach the end.
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+enum LoopPolicy {
+    #[default]
+    Forever,
+    /// Play this many times, then stay on the last frame.
+    Times(u16),
 }
 
 enum TrayMessage {
@@ -64,9 +184,12 @@ enum TrayMessage {
     Settings,
     Refresh,
     OpenCurrent,
+    FavoriteCurrent,
+    ToggleMute,
+    ToggleOverlay,
+    SwitchProfile(String),
 }
 
-const BACKGROUND_HTML: &str = include_str!(concat!(env!("OUT_DIR"), "/background.html.min"));
 const BUTTPLUG_URL: &str = "ws://127.0.0.1:12345";
 const WALLTAKER_WS_URL: &str = match option_env!("WALLTAKER_ENGINE_WS_URL") {
     Some(x) => x, None => "wss://walltaker.joi.how/cable",
@@ -118,6 +241,15 @@ async fn _main() -> Result<()> {
         .unwrap()
         .config_dir()
         .join("walltaker-engine/walltaker-engine.json");
+    let state_path = config_path.with_file_name("wallpaper.json");
+    let themes_dir = config_path.with_file_name("themes");
+    let history = history::History::load(config_path.with_file_name("history.json"));
+    let history = Rc::new(tokio::sync::Mutex::new(history));
+    let favorites_dir = directories::BaseDirs::new()
+        .unwrap()
+        .data_local_dir()
+        .join("walltaker-engine/favorites");
+    let favorites = Rc::new(tokio::sync::Mutex::new(favorites::Favorites::load(favorites_dir)?));
     let config = load_config(&config_path)?;
     let config: Rc<tokio::sync::Mutex<Config>> = tokio::sync::Mutex::new(config).into();
 
@@ -125,35 +257,83 @@ async fn _main() -> Result<()> {
 
     info!("Parsed config: {config:#?}");
     
-    let hwnds = unsafe { hwnd::find_hwnds() }?;
+    let mut workerw = unsafe { hwnd::find_workerw() }?;
+    unsafe { hwnd::watch_displays() }?;
+    let monitors = unsafe { hwnd::monitors() };
+    anyhow::ensure!(!monitors.is_empty(), "Couldn't find any monitors");
+    let monitors = Rc::new(tokio::sync::Mutex::new(monitors));
 
     let (tx, rx) = std::sync::mpsc::sync_channel(5);
     let mut tray = TrayItem::new("Walltaker Engine", IconSource::Resource("icon"))?;
     tray_items![tx, tray,
         "Open Current", TrayMessage::OpenCurrent;
+        "Favorite Current", TrayMessage::FavoriteCurrent;
         "Refresh",      TrayMessage::Refresh;
     ];
+    let mute_item = {
+        let tx = tx.clone();
+        tray.inner_mut().add_menu_item_with_id(mute_label(config.lock().await.muted), move || {
+            tx.send(TrayMessage::ToggleMute).unwrap();
+        })?
+    };
+    let overlay_item = {
+        let tx = tx.clone();
+        tray.inner_mut().add_menu_item_with_id(overlay_label(config.lock().await.overlay.enabled), move || {
+            tx.send(TrayMessage::ToggleOverlay).unwrap();
+        })?
+    };
     tray.inner_mut().add_separator()?;
     tray_items![tx, tray, "Settings", TrayMessage::Settings;];
     tray.inner_mut().add_separator()?;
-    tray_items![tx, tray, "Quit", TrayMessage::Quit;];
 
-    let mut should_play_audio = true;
-    let mut bg_webviews = Vec::new();
-    for hwnd in hwnds {
-        let webview = webview::WebView::create(Some(hwnd), should_play_audio, (100, 100))?;
-        webview.navigate_html(BACKGROUND_HTML)?;
-        set_bg_colour(&webview, &config.lock().await.background_colour)?;
-        set_fit(&config.lock().await.fit_mode, &webview)?;
-        
-        bg_webviews.push(webview);
-        should_play_audio = false;
+    let mut profile_items = Vec::new();
+    add_profile_items(&mut tray, &tx, &mut profile_items, &*config.lock().await)?;
+    if !profile_items.is_empty() {
+        tray.inner_mut().add_separator()?;
     }
 
-    let (settings, ui_rx) = webview::webviews::settings::create_settings_webview(&config)?;
+    tray_items![tx, tray, "Quit", TrayMessage::Quit;];
+
+    let cache_dir = directories::BaseDirs::new()
+        .unwrap()
+        .cache_dir()
+        .join("walltaker-engine/media");
+    let cache = Arc::new(MediaCache::new(cache_dir, config.lock().await.cache_size_mb)?);
+    let (fetched_tx, mut fetched_rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut fetch_seq = 0;
+    // Which fetch put up what's on each monitor
+    let mut shown_seq: HashMap<String, u64> = HashMap::new();
+
+    // Put the last wallpapers back up while we wait on Walltaker. We only
+    // trust them if they're in the cache (and so were checked when they went
+    // in).
+    let mut restored = state::load(&state_path);
+    restored.retain(|_, wallpaper| cache.cached(&wallpaper.url).is_some());
+    let mut shown = restored.clone();
+    cache.set_in_use(shown.values().map(|w| w.url.as_str()));
+    // The last thing shown anywhere, which is what the tray acts on
+    let mut current = state::latest(&shown).cloned();
+
+    let (bg_tx, bg_rx) = std::sync::mpsc::channel();
+    let mut screens = Vec::new();
+    refresh_screens(&mut screens, workerw, &*config.lock().await, &monitors.lock().await,
+        &cache, &themes_dir, &bg_tx, &shown)?;
+    update_collage(&*config.lock().await, &screens, &*history.lock().await, &cache, &shown)?;
+    // What each monitor had up before the last post sent to it (and the URL
+    // the page got it from), to go back to if the page can't load it
+    let mut replaced: HashMap<String, (String, Option<state::Wallpaper>)> = HashMap::new();
+    // Every monitor reports the same failure, so only complain once
+    let mut last_failed = None;
+    let mut last_theme_failure = None;
+    // Plugging in a monitor can set off a few display changes in a row, so
+    // wait for things to settle before redoing the screens
+    let mut displays_changed_at = None;
+
+    let (settings, ui_rx) = webview::webviews::settings::create_settings_webview(
+        &config, &history, &favorites, &cache, &monitors, &themes_dir)?;
 
     // We do a little hacking
-    if config.lock().await.links.is_empty() {
+    if config.lock().await.subscribed_links().is_empty() {
         let tx = tx.clone();
 
         _ = Toast::new(Toast::POWERSHELL_APP_ID)
@@ -172,7 +352,6 @@ async fn _main() -> Result<()> {
 
     let (ws_stream, _) = tokio_tungstenite::connect_async(WALLTAKER_WS_URL).await?;
     let (mut write, mut read) = ws_stream.split();
-    let mut current_url = None;
     loop {
         /* Read UI message */
         if let Ok(message) = ui_rx.try_recv() {
@@ -180,15 +359,47 @@ async fn _main() -> Result<()> {
 
             match message {
                 UiMessage::TestNotification =>
-                    notification(&*config.lock().await, &buttplug, None, 0).await,
+                    notification(&*config.lock().await, &buttplug, None, 0, false).await,
                 UiMessage::SubscribeTo(link) => walltaker::subscribe_to(&mut write, link).await?,
                 UiMessage::UnsubscribeFrom(link) =>
                     walltaker::unsubscribe_from(&mut write, link).await?,
                 UiMessage::UpdateSettings => {
-                    run_on_boot(config.lock().await.run_on_boot)?;
-                    for view in &bg_webviews {
-                        set_bg_colour(view, &config.lock().await.background_colour)?;
-                        set_fit(&config.lock().await.fit_mode, view)?;
+                    let config = config.lock().await;
+                    run_on_boot(config.run_on_boot)?;
+                    cache.set_max_mb(config.cache_size_mb);
+                    refresh_screens(&mut screens, workerw, &config, &monitors.lock().await,
+                        &cache, &themes_dir, &bg_tx, &shown)?;
+                    update_collage(&config, &screens, &*history.lock().await, &cache, &shown)?;
+                    if profile_items.is_empty() && !config.profiles.is_empty() {
+                        tray.inner_mut().add_separator()?;
+                    }
+                    add_profile_items(&mut tray, &tx, &mut profile_items, &config)?;
+                    update_profile_labels(&mut tray, &profile_items, &config)?;
+                    tray.inner_mut().set_menu_item_label(mute_label(config.muted), mute_item)?;
+                    tray.inner_mut().set_menu_item_label(overlay_label(config.overlay.enabled), overlay_item)?;
+                },
+                UiMessage::Revert(entry) => {
+                    info!("Reverting to {}", entry.url);
+                    fetch_seq += 1;
+                    spawn_fetch(&cache, &fetched_tx, fetch_seq, entry.wallpaper(), false,
+                        config.lock().await.overlay_wanted());
+                },
+                UiMessage::OpenPost(md5) => open(&format!("https://e621.net/posts?md5={md5}")),
+                UiMessage::ApplyFavorite(md5) => {
+                    let favorites = favorites.lock().await;
+                    if let Some(favorite) = favorites.get(&md5) {
+                        // Favorites might've been evicted from the cache
+                        // long ago; put them back so they go through it
+                        // like everything else.
+                        match cache.insert(&favorite.entry.url, &favorites.path_of(favorite)) {
+                            Ok(_) => {
+                                info!("Showing favorite {md5}");
+                                fetch_seq += 1;
+                                spawn_fetch(&cache, &fetched_tx, fetch_seq, favorite.entry.wallpaper(), false,
+                                    config.lock().await.overlay_wanted());
+                            },
+                            Err(e) => log::warn!("Couldn't show favorite {md5}: {e:#}"),
+                        }
                     }
                 },
             }
@@ -196,17 +407,204 @@ async fn _main() -> Result<()> {
         
         /* Read Walltaker websocket messages */
         if let Ready(Some(message)) = poll!(read.next()) {
-            let new_link = read_walltaker_message(
+            let update = read_walltaker_message(
                 &*config.lock().await,
-                &buttplug,
                 &mut write,
-                &bg_webviews,
+                &shown.values().map(|w| w.link).collect::<Vec<_>>(),
                 &message?
             ).await?;
 
-            if new_link.is_some() {
-                current_url = new_link;
+            if let Some((url, update)) = update {
+                info!("Fetching {url}");
+                fetch_seq += 1;
+                let wallpaper = state::Wallpaper::new(&url, &update);
+                spawn_fetch(&cache, &fetched_tx, fetch_seq, wallpaper, true,
+                    config.lock().await.overlay_wanted());
+            }
+        }
+
+        /* Show posts once they've downloaded */
+        match fetched_rx.try_recv() {
+            Ok(Fetch::Cached(Fetched { seq, wallpaper, from_walltaker, cached })) => {
+                let url = &wallpaper.url;
+
+                match cached {
+                    Ok(cached) => {
+                        // Downloads can finish out of order; don't let a slow old
+                        // post clobber a newer one.
+                        let targets: Vec<_> = config.lock().await.route(wallpaper.link, &screens)
+                            .into_iter()
+                            .filter(|s| shown_seq.get(&s.monitor.id).map_or(true, |&n| seq > n))
+                            .collect();
+                        for screen in &targets {
+                            shown_seq.insert(screen.monitor.id.clone(), seq);
+                        }
+
+                        // The first post from Walltaker after startup is checked
+                        // against what we restored; if it's the same there's
+                        // nothing to do.
+                        let away: Vec<_> = if from_walltaker {
+                            targets.iter().filter_map(|s| restored.remove(&s.monitor.id)).collect()
+                        } else {
+                            Vec::new()
+                        };
+
+                        if targets.is_empty() {
+                            log::debug!("Skipping {url}, something newer's already up");
+                        } else if !away.is_empty() && away.iter().all(|w| &w.url == url) {
+                            info!("Wallpaper hasn't changed since last time");
+                        } else {
+                            let kind = show_wallpaper(&targets, &wallpaper, cached.as_deref())?;
+                            let src = page_url(url, cached.as_deref());
+
+                            if from_walltaker {
+                                if kind == MediaKind::Unsupported {
+                                    toast("Someone set a post Walltaker Engine can't show. Use \"Open Current\" to see it on e621.");
+                                }
+
+                                let config = config.lock().await;
+                                notification(&config, &buttplug,
+                                    wallpaper.set_by.clone(), wallpaper.link, !away.is_empty()).await;
+
+                                let entry = history::Entry::new(&wallpaper, kind);
+                                if let Err(e) = history.lock().await.push(entry, config.history_limit) {
+                                    log::warn!("Couldn't save wallpaper history: {e}");
+                                }
+
+                                if let (true, Some(cached)) = (config.archive.enabled, cached) {
+                                    let (settings, wallpaper) = (config.archive.clone(), wallpaper.clone());
+                                    tokio::task::spawn_blocking(move || {
+                                        match archive::save(&settings, &wallpaper, &cached) {
+                                            Ok(Some(to)) => info!("Archived to {}", to.display()),
+                                            Ok(None) => { },
+                                            Err(e) => log::warn!("Couldn't archive {}: {e:#}", wallpaper.url),
+                                        }
+                                    });
+                                }
+                            }
+
+                            for screen in &targets {
+                                let id = &screen.monitor.id;
+                                let previous = shown.insert(id.clone(), wallpaper.clone());
+                                replaced.insert(id.clone(), (src.clone(), previous));
+                            }
+                            cache.set_in_use(shown.values().map(|w| w.url.as_str()));
+                            update_collage(&*config.lock().await, &screens, &*history.lock().await,
+                                &cache, &shown)?;
+                            if let Err(e) = state::save(&state_path, &shown) {
+                                log::warn!("Couldn't save the current wallpaper: {e}");
+                            }
+                            current = Some(wallpaper);
+                        }
+                    },
+                    // Never show something that didn't check out
+                    Err(e) => log::error!("Couldn't fetch {url}, keeping the current wallpaper: {e:#}"),
+                }
+            },
+
+            // Fill in post details as they come back from e621
+            Ok(Fetch::LookedUp(wallpaper)) => {
+                let is_it = |w: &state::Wallpaper| w.url == wallpaper.url && w.set_at == wallpaper.set_at;
+                let on: Vec<_> = shown.iter()
+                    .filter(|(_, w)| is_it(w))
+                    .map(|(id, _)| id.clone())
+                    .collect();
+
+                for id in &on {
+                    shown.insert(id.clone(), wallpaper.clone());
+                }
+                for screen in screens.iter().filter(|s| on.contains(&s.monitor.id)) {
+                    background::send(&screen.view, &Command::Info { wallpaper: &wallpaper })?;
+                }
+                if current.as_ref().is_some_and(is_it) {
+                    current = Some(wallpaper.clone());
+                }
+
+                if !on.is_empty() {
+                    if let Err(e) = state::save(&state_path, &shown) {
+                        log::warn!("Couldn't save the current wallpaper: {e}");
+                    }
+                }
+                if let Err(e) = history.lock().await.update_post(&wallpaper) {
+                    log::warn!("Couldn't save wallpaper history: {e}");
+                }
+            },
+
+            Err(_) => { },
+        }
+
+        /* Read background page events */
+        match bg_rx.try_recv() {
+            Ok(background::Event::LoadFailed { monitor, url, reason }) => {
+                log::warn!("Couldn't load {url} on {monitor}, keeping the old wallpaper: {reason}");
+
+                // The page only reports the last thing it was sent, but that
+                // might not be the last thing we sent yet
+                if replaced.get(&monitor).is_some_and(|(sent, _)| *sent == url) {
+                    if let Some((_, previous)) = replaced.remove(&monitor) {
+                        match previous {
+                            Some(previous) => shown.insert(monitor, previous),
+                            None => shown.remove(&monitor),
+                        };
+                        current = state::latest(&shown).cloned();
+                        cache.set_in_use(shown.values().map(|w| w.url.as_str()));
+                        if let Err(e) = state::save(&state_path, &shown) {
+                            log::warn!("Couldn't save the current wallpaper: {e}");
+                        }
+                        update_collage(&*config.lock().await, &screens, &*history.lock().await,
+                            &cache, &shown)?;
+                    }
+                }
+
+                if last_failed.as_ref() != Some(&url) {
+                    toast(&format!("Couldn't show the new wallpaper because {reason}."));
+                    last_failed = Some(url);
+                }
+            },
+            Ok(background::Event::ThemeFailed { monitor, reason }) => {
+                log::warn!("Background theme failed on {monitor}, using the built-in one: {reason}");
+                if last_theme_failure.as_ref() != Some(&reason) {
+                    toast(&format!("Your background theme isn't working, so the built-in one's being used instead: {reason}"));
+                    last_theme_failure = Some(reason);
+                }
+
+                if let Some(screen) = screens.iter_mut().find(|s| s.monitor.id == monitor) {
+                    if screen.fall_back()? {
+                        let config = config.lock().await;
+                        apply_display_settings(&config, &screens)?;
+                        if let Some(screen) = screens.iter().find(|s| s.monitor.id == monitor) {
+                            show_current(screen, &cache, &shown)?;
+                        }
+                        update_collage(&config, &screens, &*history.lock().await, &cache, &shown)?;
+                    }
+                }
+            },
+            Err(_) => { },
+        }
+
+        /* Follow monitors being plugged in, unplugged or changed */
+        if hwnd::displays_changed() {
+            displays_changed_at = Some(std::time::Instant::now());
+        }
+        if displays_changed_at.is_some_and(|at| at.elapsed() > Duration::from_secs(1)) {
+            displays_changed_at = None;
+
+            let mut monitors = monitors.lock().await;
+            *monitors = unsafe { hwnd::monitors() };
+            info!("Monitors are now {monitors:#?}");
+
+            // Explorer sometimes makes a new WorkerW when the layout changes
+            let new_workerw = unsafe { hwnd::find_workerw() }?;
+            if new_workerw != workerw {
+                workerw = new_workerw;
+                for screen in screens.drain(..) {
+                    screen.close();
+                }
             }
+
+            let config = config.lock().await;
+            refresh_screens(&mut screens, workerw, &config, &monitors, &cache, &themes_dir, &bg_tx, &shown)?;
+            update_collage(&config, &screens, &*history.lock().await, &cache, &shown)?;
         }
 
         /* Read tray messages */
@@ -222,58 +620,125 @@ async fn _main() -> Result<()> {
                 },
         
                 TrayMessage::Refresh => {
-                    if let Some(link) = config.lock().await.links.choose(&mut rand::thread_rng()) {
+                    if let Some(link) = config.lock().await.subscribed_links().choose(&mut rand::thread_rng()) {
                         walltaker::check(&mut write, *link).await?;
                     }
                 },
                 
                 TrayMessage::OpenCurrent => {
-                    if let Some(ref current_url) = current_url {
-                        let md5 = current_url
-                            .file_stem()
-                            .ok_or_else(|| anyhow::anyhow!("current_url has no stem!"))?
-                            .to_string_lossy();
+                    if let Some(ref current) = current {
+                        let md5 = MediaCache::md5_of(&current.url)
+                            .ok_or_else(|| anyhow::anyhow!("current wallpaper has no md5!"))?;
                         
                         let url = format!("https://e621.net/posts?md5={md5}");
                         open(&url);
                     }
                 },
+
+                TrayMessage::FavoriteCurrent => {
+                    if let Some(ref current) = current {
+                        let added = match cache.cached(&current.url) {
+                            Some(cached) => favorites.lock().await
+                                .add(current, media::detect(&current.url, Some(&cached)), &cached),
+                            None => Err(anyhow::anyhow!("it isn't in the cache")),
+                        };
+
+                        match added {
+                            Ok(true) => toast("Added the current wallpaper to your favorites ❤️"),
+                            Ok(false) => toast("The current wallpaper's already a favorite"),
+                            Err(e) => {
+                                log::warn!("Couldn't favorite {}: {e:#}", current.url);
+                                toast("Couldn't favorite the current wallpaper");
+                            },
+                        }
+                    }
+                },
+
+                TrayMessage::ToggleMute => {
+                    let mut config = config.lock().await;
+                    config.muted = !config.muted;
+                    info!("Muted: {}", config.muted);
+
+                    apply_display_settings(&config, &screens)?;
+                    tray.inner_mut().set_menu_item_label(mute_label(config.muted), mute_item)?;
+                },
+
+                TrayMessage::ToggleOverlay => {
+                    let mut config = config.lock().await;
+                    config.overlay.enabled = !config.overlay.enabled;
+
+                    apply_display_settings(&config, &screens)?;
+                    tray.inner_mut().set_menu_item_label(overlay_label(config.overlay.enabled), overlay_item)?;
+                },
+
+                TrayMessage::SwitchProfile(name) => {
+                    let mut config = config.lock().await;
+                    let old_links = config.subscribed_links();
+                    if config.switch_profile(&name) {
+                        info!("Switched to profile {name}");
+
+                        let new_links = config.subscribed_links();
+                        for link in old_links.iter().filter(|l| !new_links.contains(l)) {
+                            walltaker::unsubscribe_from(&mut write, *link).await?;
+                        }
+                        for link in new_links.iter().filter(|l| !old_links.contains(l)) {
+                            walltaker::subscribe_to(&mut write, *link).await?;
+                        }
+
+                        apply_display_settings(&config, &screens)?;
+                        update_profile_labels(&mut tray, &profile_items, &config)?;
+                    } else {
+                        log::warn!("Tried to switch to missing profile {name}");
+                    }
+                },
             }
         }
 
         settings.handle_messages()?;
-        for view in &bg_webviews {
-            view.handle_messages()?;
+        for screen in &screens {
+            screen.view.handle_messages()?;
         }
 
         tokio::time::sleep(Duration::from_millis(20)).await;
     }
 }
 
+/// What a fresh install starts with.
+fn default_config() -> Config {
+    Config {
+        notifications: true,
+        debug_logs: true,
+        background_colour: String::from("#202640"),
+        auto_fit_threshold: default_auto_fit_threshold(),
+        cache_size_mb: default_cache_size_mb(),
+        history_limit: default_history_limit(),
+        load_timeout_secs: default_load_timeout_secs(),
+        transition_ms: default_transition_ms(),
+        volume: default_volume(),
+        playback_speed: default_playback_speed(),
+        ..Default::default()
+    }
+}
+
 fn load_config<P: AsRef<Path>>(from: P) -> Result<Config> {
     let mut config: Config = if let Ok(file) = File::open(&from) {
         serde_json::from_reader(file)?
     } else {
-        // Default configuration
-        Config {
-            notifications: true,
-            debug_logs: true,
-            background_colour: String::from("#202640"),
-            ..Default::default()
-        }
+        default_config()
     };
     config.version = format!("v{}", env!("CARGO_PKG_VERSION"));
 
     Ok(config)
 }
 
+/// Handles housekeeping messages itself and returns the post URL and update
+/// for wallpaper changes.
 async fn read_walltaker_message(
     config: &Config,
-    buttplug: &ButtplugClient,
     writer: &mut Writer,
-    bg_webviews: &[webview::WebView],
+    last_links: &[usize],
     message: &Message
-) -> Result<Option<PathBuf>>
+) -> Result<Option<(String, walltaker::WallpaperUpdate)>>
 {
     use walltaker::Incoming;
 
@@ -299,15 +764,28 @@ async fn read_walltaker_message(
         Incoming::Welcome => {
             info!("Connected to Walltaker");
 
-            for link in &config.links {
+            let subscribed = config.subscribed_links();
+            for link in &subscribed {
                 walltaker::subscribe_to(writer, *link).await?;
             }
 
-            if let Some(link) = config.links.choose(&mut rand::thread_rng()) {
+            // Links with a monitor to themselves all get checked so every
+            // monitor's up to date. For the rest, whoever set what we restored
+            // is the one to check with, if they're still around.
+            let (mut links, unrouted): (Vec<usize>, Vec<usize>) = subscribed.into_iter()
+                .partition(|l| config.is_routed(*l));
+            let unrouted = last_links.iter()
+                .find(|l| unrouted.contains(l))
+                .or_else(|| unrouted.choose(&mut rand::thread_rng()));
+            links.extend(unrouted);
+
+            if !links.is_empty() {
                 // Not the best but it works and whatnot
                 tokio::time::sleep(Duration::from_millis(1000)).await;
+            }
+            for link in links {
                 info!("Checking link {link} for initial wallpaper");
-                walltaker::check(writer, *link).await?;
+                walltaker::check(writer, link).await?;
             }
         },
 
@@ -317,25 +795,8 @@ async fn read_walltaker_message(
 
         // Wallpaper change
         Incoming::Message { message, .. } => {
-            if let Some(url) = message.post_url {
-                info!("Changing wallpaper to {url}");
-                let url_path = PathBuf::from(&url);
-                let ext = url_path.extension().unwrap().to_string_lossy().to_lowercase();
-
-                // just awful
-                let element =           if ext == "webm" { "video" } else { "image" };
-                let the_other_element = if ext == "webm" { "image" } else { "video" };
-                
-                for view in bg_webviews {
-                    view.eval(&format!("
-                        document.getElementById('{element}').src = '{url}';
-                        document.getElementById('{the_other_element}').src = '';
-                    "))?;
-                }
-
-                notification(config, buttplug, message.set_by, message.id).await;
-
-                return Ok(Some(url_path));
+            if let Some(url) = message.post_url.clone() {
+                return Ok(Some((url, message)));
             }
         }
     }
@@ -343,17 +804,89 @@ async fn read_walltaker_message(
     Ok(None)
 }
 
+/// Put `wallpaper` through the cache. If `want_post` and we don't know them
+/// already, its details are looked up on e621 alongside, but never held up
+/// for.
+fn spawn_fetch(
+    cache: &Arc<MediaCache>,
+    fetched_tx: &tokio::sync::mpsc::UnboundedSender<Fetch>,
+    seq: u64,
+    wallpaper: state::Wallpaper,
+    from_walltaker: bool,
+    want_post: bool
+) {
+    let (cache, fetched_tx) = (Arc::clone(cache), fetched_tx.clone());
+    tokio::spawn(async move {
+        // Only the overlay needs the post's details, so don't bother e621
+        // otherwise
+        let lookup = MediaCache::md5_of(&wallpaper.url)
+            .filter(|_| want_post && wallpaper.post.is_none())
+            .map(|md5| tokio::spawn(async move { e621::lookup(&md5).await }));
+
+        let mut looked_up = wallpaper.clone();
+        let cached = cache.fetch(&wallpaper.url).await;
+        _ = fetched_tx.send(Fetch::Cached(Fetched { seq, wallpaper, from_walltaker, cached }));
+
+        let Some(lookup) = lookup else { return };
+        match lookup.await {
+            Ok(Ok(Some(post))) => {
+                looked_up.post = Some(post);
+                _ = fetched_tx.send(Fetch::LookedUp(looked_up));
+            },
+            Ok(Err(e)) => log::warn!("Couldn't look {} up on e621: {e:#}", looked_up.url),
+            // Not on e621, or the lookup panicked
+            Ok(Ok(None)) | Err(_) => { },
+        }
+    });
+}
+
+/// `cached` is where the cache put the post, if it could be checked and
+/// cached at all. Returns what kind of post it turned out to be.
+fn show_wallpaper(
+    screens: &[&Screen],
+    wallpaper: &state::Wallpaper,
+    cached: Option<&Path>
+) -> Result<MediaKind> {
+    let url = &wallpaper.url;
+    info!("Changing wallpaper to {url}");
+    let kind = media::detect(url, cached);
+
+    if kind == MediaKind::Unsupported {
+        log::warn!("Can't show {url}, showing a placeholder");
+    }
+
+    let src = page_url(url, cached);
+    for screen in screens {
+        background::send(&screen.view, &Command::Media { url: &src, kind })?;
+        background::send(&screen.view, &Command::Info { wallpaper })?;
+    }
+
+    Ok(kind)
+}
+
+/// Where the page loads `url` from: our copy if there is one.
+fn page_url(url: &str, cached: Option<&Path>) -> String {
+    cached
+        .and_then(MediaCache::local_url)
+        .unwrap_or_else(|| String::from(url))
+}
+
 async fn notification(
     config: &Config,
     buttplug: &ButtplugClient,
     set_by: Option<String>,
-    id: usize
+    id: usize,
+    while_away: bool
 ) {
     if config.notifications {
         let set_by = set_by
             .unwrap_or_else(|| String::from("Anonymous"));
 
-        let notif = format!("{set_by} changed your wallpaper via link {id}! ❤️");
+        let notif = if while_away {
+            format!("{set_by} changed your wallpaper via link {id} while you were away! ❤️")
+        } else {
+            format!("{set_by} changed your wallpaper via link {id}! ❤️")
+        };
 
         _ = Toast::new(Toast::POWERSHELL_APP_ID)
             .title("Walltaker Engine")
@@ -400,22 +933,199 @@ fn init_logging(write: bool) -> Result<()> {
     Ok(())
 }
  
-fn set_fit(mode: &FitMode, to: &webview::WebView) -> webview::Result<()> {
-    to.eval(match mode {
-        FitMode::Stretch => "setStretch();",
-        FitMode::Fill => "setFill();",
-        FitMode::Fit => "setFit();",
+fn apply_display_settings(config: &Config, screens: &[Screen]) -> Result<()> {
+    for screen in screens {
+        let id = &screen.monitor.id;
+        background::send(&screen.view, &Command::Colour { colour: config.colour_for(id) })?;
+        background::send(&screen.view, &Command::Fit {
+            mode: config.fit_mode_for(id),
+            auto_threshold: config.auto_fit_threshold,
+        })?;
+        background::send(&screen.view, &Command::Adjustments {
+            adjustments: config.adjustments_for(id),
+        })?;
+        background::send(&screen.view, &Command::Overlay {
+            enabled: config.overlay_for(id),
+            settings: &config.overlay,
+        })?;
+    }
+    background::send_all(screens, &Command::Letterbox { mode: &config.letterbox })?;
+    background::send_all(screens, &Command::Loading {
+        indicator: config.loading_indicator,
+        timeout_ms: config.load_timeout_secs.saturating_mul(1000),
+    })?;
+    background::send_all(screens, &Command::Transition {
+        kind: &config.transition,
+        duration_ms: config.transition_ms,
     })?;
+    background::send_all(screens, &Command::Playback {
+        volume: config.volume,
+        speed: config.playback_speed,
+        loop_policy: &config.loop_policy,
+    })?;
+
+    // Only one monitor plays sound, or everything echoes
+    let audio = config.audio_monitor(screens.iter().map(|s| &s.monitor));
+    for screen in screens {
+        screen.view.set_muted(config.muted || audio != Some(screen.monitor.id.as_str()))?;
+    }
 
     Ok(())
 }
 
-fn set_bg_colour(view: &webview::WebView, color: &str) -> Result<()> {
-    view.eval(&format!("document.body.style.backgroundColor = '{color}';"))?;
-    
+/// Refill the collage tiles on every screen around whatever it's showing.
+fn update_collage(
+    config: &Config,
+    screens: &[Screen],
+    history: &history::History,
+    cache: &MediaCache,
+    shown: &state::Shown,
+) -> Result<()> {
+    let settings = &config.collage;
+    for screen in screens {
+        let tiles = if settings.enabled {
+            let current = shown.get(&screen.monitor.id)
+                .or_else(|| state::latest(shown))
+                .map(|w| w.url.as_str());
+            collage::tiles(settings, history, cache, current)
+        } else {
+            Vec::new()
+        };
+
+        background::send(&screen.view, &Command::Collage {
+            enabled: settings.enabled,
+            columns: settings.columns,
+            rows: settings.rows,
+            fit: &settings.tile_fit,
+            tiles: &tiles,
+        })?;
+    }
+
     Ok(())
 }
 
+/// Make sure there's a screen for every enabled monitor and nothing else,
+/// putting what was `shown` back up on any that had to be made. Screens that
+/// still fit are left alone. Then applies the display settings.
+#[allow(clippy::too_many_arguments)]
+fn refresh_screens(
+    screens: &mut Vec<Screen>,
+    workerw: HWND,
+    config: &Config,
+    monitors: &[hwnd::Monitor],
+    cache: &MediaCache,
+    themes: &Path,
+    events: &std::sync::mpsc::Sender<background::Event>,
+    shown: &state::Shown,
+) -> Result<()> {
+    let enabled = monitors.iter().filter(|m| config.monitor_enabled(&m.id));
+    let wanted: Vec<_> = if config.span {
+        hwnd::Monitor::span(enabled).into_iter().collect()
+    } else {
+        enabled.cloned().collect()
+    };
+    let theme = config.theme.as_deref().map(|name| themes.join(name));
+
+    let mut old = std::mem::take(screens);
+    // Indexes into `screens` of the ones made just now
+    let mut fresh = Vec::new();
+    for monitor in wanted {
+        if let Some(i) = old.iter().position(|s| s.monitor == monitor && s.theme == theme) {
+            screens.push(old.swap_remove(i));
+        } else {
+            info!("Setting up a screen for {}", monitor.id);
+            fresh.push(screens.len());
+            screens.push(Screen::create(workerw, monitor, cache.dir(), theme.as_deref(), events)?);
+        }
+    }
+    for screen in old {
+        info!("Closing the screen for {}", screen.monitor.id);
+        screen.close();
+    }
+
+    apply_display_settings(config, screens)?;
+
+    for i in fresh {
+        show_current(&screens[i], cache, shown)?;
+    }
+
+    Ok(())
+}
+
+/// Put back what `screen` had up on a fresh page. Monitors we've not shown
+/// anything on yet get the latest wallpaper.
+fn show_current(screen: &Screen, cache: &MediaCache, shown: &state::Shown) -> Result<()> {
+    let wallpaper = shown.get(&screen.monitor.id).or_else(|| state::latest(shown));
+    if let Some(wallpaper) = wallpaper {
+        show_wallpaper(&[screen], wallpaper, cache.cached(&wallpaper.url).as_deref())?;
+    }
+
+    Ok(())
+}
+
+fn mute_label(muted: bool) -> &'static str {
+    if muted { "Unmute" } else { "Mute" }
+}
+
+fn overlay_label(enabled: bool) -> &'static str {
+    if enabled { "Hide Info" } else { "Show Info" }
+}
+
+/// Add tray items for any profiles in `config` that don't have one yet.
+/// tray-item can't make submenus or insert, so ones made while running end
+/// up at the bottom of the menu.
+fn add_profile_items(
+    tray: &mut TrayItem,
+    tx: &std::sync::mpsc::SyncSender<TrayMessage>,
+    items: &mut Vec<(String, u32)>,
+    config: &Config
+) -> Result<()> {
+    for profile in &config.profiles {
+        if items.iter().any(|(name, _)| *name == profile.name) {
+            continue;
+        }
+        if items.is_empty() {
+            tray.add_label("Profiles")?;
+        }
+
+        let tx = tx.clone();
+        let name = profile.name.clone();
+        let label = profile::tray_label(&name, config.active_profile.as_deref());
+        let id = tray.inner_mut().add_menu_item_with_id(&label, move || {
+            tx.send(TrayMessage::SwitchProfile(name.clone())).unwrap();
+        })?;
+
+        items.push((profile.name.clone(), id));
+    }
+
+    Ok(())
+}
+
+/// tray-item can't remove items either, so deleted profiles just say so.
+fn update_profile_labels(
+    tray: &mut TrayItem,
+    items: &[(String, u32)],
+    config: &Config
+) -> Result<()> {
+    for (name, id) in items {
+        let label = if config.profiles.iter().any(|p| p.name == *name) {
+            profile::tray_label(name, config.active_profile.as_deref())
+        } else {
+            format!("    {name} (deleted)")
+        };
+        tray.inner_mut().set_menu_item_label(&label, *id)?;
+    }
+
+    Ok(())
+}
+
+fn toast(text: &str) {
+    _ = Toast::new(Toast::POWERSHELL_APP_ID)
+        .title("Walltaker Engine")
+        .text1(text)
+        .show();
+}
+
 fn open(url: &str) {
     unsafe {
         ShellExecuteW(
@@ -0,0 +1,172 @@
+//! Working out what a post actually is. e621 mostly names files sensibly but
+//! not always, and some of what it hosts (Flash, mainly) can't be shown at
+//! all.
+use std::{fs::File, io::Read, path::Path};
+use serde::{Serialize, Deserialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    Image,
+    Video,
+    /// Something the background page can't play.
+    Unsupported,
+}
+
+/// Everything the background page can show, by extension.
+const IMAGES: &[&str] = &["jpg", "jpeg", "png", "apng", "gif", "webp", "avif", "bmp"];
+const VIDEOS: &[&str] = &["webm", "mp4", "m4v", "ogv"];
+
+/// The lowercased extension of the file a URL points to, if it has one.
+pub fn extension(url: &str) -> Option<String> {
+    let name = url.split(['?', '#']).next()?.rsplit('/').next()?;
+    let (_, ext) = name.rsplit_once('.')?;
+
+    (!ext.is_empty()).then(|| ext.to_lowercase())
+}
+
+pub fn kind_from_extension(ext: &str) -> MediaKind {
+    if IMAGES.contains(&ext) {
+        MediaKind::Image
+    } else if VIDEOS.contains(&ext) {
+        MediaKind::Video
+    } else {
+        MediaKind::Unsupported
+    }
+}
+
+/// Guess what a file is from its first few bytes. Returns its kind and the
+/// extension it should have.
+pub fn sniff(bytes: &[u8]) -> Option<(MediaKind, &'static str)> {
+    let at = |offset: usize, magic: &[u8]| bytes.get(offset..offset + magic.len()) == Some(magic);
+
+    Some(if at(0, b"\xFF\xD8\xFF") {
+        (MediaKind::Image, "jpg")
+    } else if at(0, b"\x89PNG\r\n\x1A\n") {
+        (MediaKind::Image, "png")
+    } else if at(0, b"GIF87a") || at(0, b"GIF89a") {
+        (MediaKind::Image, "gif")
+    } else if at(0, b"RIFF") && at(8, b"WEBP") {
+        (MediaKind::Image, "webp")
+    } else if at(4, b"ftypavif") || at(4, b"ftypavis") {
+        (MediaKind::Image, "avif")
+    } else if at(0, b"BM") {
+        (MediaKind::Image, "bmp")
+    } else if at(4, b"ftyp") {
+        (MediaKind::Video, "mp4")
+    } else if at(0, b"\x1A\x45\xDF\xA3") {
+        (MediaKind::Video, "webm")
+    } else if at(0, b"OggS") {
+        (MediaKind::Video, "ogv")
+    } else if at(0, b"FWS") || at(0, b"CWS") || at(0, b"ZWS") {
+        (MediaKind::Unsupported, "swf")
+    } else {
+        return None;
+    })
+}
+
+fn sniff_file(path: &Path) -> Option<MediaKind> {
+    let mut head = Vec::with_capacity(16);
+    File::open(path).ok()?.take(16).read_to_end(&mut head).ok()?;
+
+    sniff(&head).map(|(kind, _)| kind)
+}
+
+/// What kind of post `url` is. `cached` is our copy of it, if we have one.
+pub fn detect(url: &str, cached: Option<&Path>) -> MediaKind {
+    // The cache names files by what they really are, so trust that first
+    let ext = cached
+        .and_then(|path| path.extension())
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .or_else(|| extension(url));
+
+    if let Some(kind) = ext.as_deref().map(kind_from_extension) {
+        if kind != MediaKind::Unsupported {
+            return kind;
+        }
+    }
+
+    // Unknown or no extension, so go by the file itself
+    match cached.and_then(sniff_file) {
+        Some(kind) => kind,
+        None if ext.is_some() || cached.is_some() => MediaKind::Unsupported,
+        // Nothing to go on at all; let the page have a go
+        None => MediaKind::Image,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    /// What a file starting with these bytes sniffs as.
+    type Sniffed = Option<(MediaKind, &'static str)>;
+
+    /// A cached file called `name` starting with `head`.
+    fn cached(dir: &TempDir, name: &str, head: &[u8]) -> std::path::PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, head).unwrap();
+        path
+    }
+
+    #[test]
+    fn sniffs_magic_numbers() {
+        let cases: &[(&[u8], Sniffed)] = &[
+            (b"\xFF\xD8\xFF\xE0", Some((MediaKind::Image, "jpg"))),
+            (b"\x89PNG\r\n\x1A\n", Some((MediaKind::Image, "png"))),
+            (b"GIF89a", Some((MediaKind::Image, "gif"))),
+            (b"RIFF\0\0\0\0WEBPVP8 ", Some((MediaKind::Image, "webp"))),
+            (b"\0\0\0\x1CftypavifX", Some((MediaKind::Image, "avif"))),
+            (b"\0\0\0\x18ftypmp42", Some((MediaKind::Video, "mp4"))),
+            (b"\x1A\x45\xDF\xA3", Some((MediaKind::Video, "webm"))),
+            (b"OggS", Some((MediaKind::Video, "ogv"))),
+            (b"CWS\x0A", Some((MediaKind::Unsupported, "swf"))),
+            (b"<html>", None),
+            (b"", None),
+            // Too short to hold the magic it starts like
+            (b"RIFF", None),
+            (b"\x89PN", None),
+        ];
+
+        for (bytes, expected) in cases {
+            assert_eq!(sniff(bytes), *expected, "{bytes:?}");
+        }
+    }
+
+    #[test]
+    fn extensions() {
+        assert_eq!(extension("https://e621.net/a/b.PNG?x=1.webm").as_deref(), Some("png"));
+        assert_eq!(extension("https://e621.net/a/b.webm#t=1.5").as_deref(), Some("webm"));
+        assert_eq!(extension("https://e621.net/a.b/c"), None);
+        assert_eq!(extension("https://e621.net/a/b."), None);
+    }
+
+    #[test]
+    fn detect_by_url() {
+        assert_eq!(detect("https://e621.net/a.jpg", None), MediaKind::Image);
+        assert_eq!(detect("https://e621.net/a.webm", None), MediaKind::Video);
+        assert_eq!(detect("https://e621.net/a.swf", None), MediaKind::Unsupported);
+        // Nothing to go on; the page gets a go
+        assert_eq!(detect("https://e621.net/a", None), MediaKind::Image);
+    }
+
+    #[test]
+    fn detect_trusts_the_cache() {
+        let dir = TempDir::new("media");
+
+        // Cached under what it turned out to be
+        let path = cached(&dir, "a.webm", b"\x1A\x45\xDF\xA3");
+        assert_eq!(detect("https://e621.net/a.png", Some(&path)), MediaKind::Video);
+
+        // Cached with no useful extension, so sniffed
+        let path = cached(&dir, "b.bin", b"\x89PNG\r\n\x1A\n");
+        assert_eq!(detect("https://e621.net/b", Some(&path)), MediaKind::Image);
+
+        let path = cached(&dir, "c.bin", b"FWS");
+        assert_eq!(detect("https://e621.net/c", Some(&path)), MediaKind::Unsupported);
+
+        let path = cached(&dir, "d.bin", b"who knows");
+        assert_eq!(detect("https://e621.net/d", Some(&path)), MediaKind::Unsupported);
+    }
+}
@@ -0,0 +1,195 @@
+//! Settings that can differ between monitors. Monitors are matched up with
+//! their settings by [`Monitor::id`]; anything left unset follows the main
+//! config.
+use serde::{Serialize, Deserialize};
+
+use crate::{background::{Adjustments, Screen}, hwnd::Monitor, Config, FitMode};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub id: String,
+    /// What the monitor was called when these were saved, so they can be
+    /// told apart in the config file.
+    pub name: String,
+    pub enabled: bool,
+    pub fit_mode: Option<FitMode>,
+    pub background_colour: Option<String>,
+    pub adjustments: Option<Adjustments>,
+    /// Show the info overlay here regardless of the main setting.
+    pub overlay: Option<bool>,
+    /// Play sound from this monitor. Only one can at a time.
+    pub audio: bool,
+    /// Links whose posts go to this monitor. See [`Config::route`].
+    pub links: Vec<usize>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            id: String::new(),
+            name: String::new(),
+            enabled: true,
+            fit_mode: None,
+            background_colour: None,
+            adjustments: None,
+            overlay: None,
+            audio: false,
+            links: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn monitor(&self, id: &str) -> Option<&Settings> {
+        self.monitors.iter().find(|m| m.id == id)
+    }
+
+    /// Monitors we've never seen before are on.
+    pub fn monitor_enabled(&self, id: &str) -> bool {
+        self.monitor(id).map_or(true, |m| m.enabled)
+    }
+
+    pub fn fit_mode_for(&self, id: &str) -> &FitMode {
+        self.monitor(id)
+            .and_then(|m| m.fit_mode.as_ref())
+            .unwrap_or(&self.fit_mode)
+    }
+
+    pub fn colour_for(&self, id: &str) -> &str {
+        self.monitor(id)
+            .and_then(|m| m.background_colour.as_deref())
+            .unwrap_or(&self.background_colour)
+    }
+
+    pub fn adjustments_for(&self, id: &str) -> &Adjustments {
+        self.monitor(id)
+            .and_then(|m| m.adjustments.as_ref())
+            .unwrap_or(&self.adjustments)
+    }
+
+    /// Which of `monitors` should play sound: the one picked for it, or the
+    /// first if that one's not around.
+    pub fn audio_monitor<'a>(&self, mut monitors: impl Iterator<Item = &'a Monitor>) -> Option<&'a str> {
+        let mut first = None;
+        monitors.find_map(|monitor| {
+            first.get_or_insert(monitor.id.as_str());
+            self.monitor(&monitor.id)
+                .is_some_and(|m| m.audio)
+                .then_some(monitor.id.as_str())
+        }).or(first)
+    }
+
+    /// Whether any monitor has `link` to itself.
+    pub fn is_routed(&self, link: usize) -> bool {
+        self.monitors.iter().any(|m| m.links.contains(&link))
+    }
+
+    /// Everything that needs subscribing to: the main links, and any a
+    /// monitor's been given that aren't among them.
+    pub fn subscribed_links(&self) -> Vec<usize> {
+        let mut links = self.links.clone();
+        for link in self.monitors.iter().flat_map(|m| &m.links) {
+            if !links.contains(link) {
+                links.push(*link);
+            }
+        }
+
+        links
+    }
+
+    /// Which of `screens` posts from `link` should go to. Links a monitor's
+    /// been given go only there. Anything else goes to the default monitor if
+    /// one's picked, or else to every monitor without links of its own (or
+    /// every monitor, if they've all got some).
+    pub fn route<'a>(&self, link: usize, screens: &'a [Screen]) -> Vec<&'a Screen> {
+        self.route_by(link, screens, |screen| screen.monitor.id.as_str())
+    }
+
+    /// [`Config::route`] for anything that knows which monitor it's on.
+    fn route_by<'a, T>(&self, link: usize, items: &'a [T], id: impl Fn(&T) -> &str) -> Vec<&'a T> {
+        let links_of = |item: &T| self.monitor(id(item))
+            .map_or(&[][..], |m| m.links.as_slice());
+
+        let assigned: Vec<_> = items.iter().filter(|i| links_of(i).contains(&link)).collect();
+        if !assigned.is_empty() {
+            return assigned;
+        }
+
+        let default: Vec<_> = match &self.default_monitor {
+            Some(default) => items.iter().filter(|i| id(i) == default).collect(),
+            None => items.iter().filter(|i| links_of(i).is_empty()).collect(),
+        };
+        if default.is_empty() {
+            items.iter().collect()
+        } else {
+            default
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MONITORS: &[&str] = &["left", "middle", "right"];
+
+    fn config(links: &[usize], monitors: &[(&str, &[usize])]) -> Config {
+        let mut config = crate::default_config();
+        config.links = links.to_vec();
+        config.monitors = monitors.iter()
+            .map(|&(id, links)| Settings { id: String::from(id), links: links.to_vec(), ..Settings::default() })
+            .collect();
+        config
+    }
+
+    fn route(config: &Config, link: usize) -> Vec<&'static str> {
+        config.route_by(link, MONITORS, |id| *id).into_iter().copied().collect()
+    }
+
+    #[test]
+    fn monitor_links_are_subscribed() {
+        let config = config(&[1, 2], &[("left", &[2, 3]), ("right", &[4, 3])]);
+        assert_eq!(config.subscribed_links(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn unrouted_links_go_everywhere() {
+        let config = config(&[1], &[]);
+        assert_eq!(route(&config, 1), MONITORS);
+    }
+
+    #[test]
+    fn routed_links_go_only_to_their_monitors() {
+        let config = config(&[1], &[("left", &[2]), ("right", &[2, 3])]);
+        assert_eq!(route(&config, 2), ["left", "right"]);
+        assert_eq!(route(&config, 3), ["right"]);
+    }
+
+    #[test]
+    fn other_links_skip_monitors_with_their_own() {
+        let config = config(&[1], &[("left", &[2])]);
+        assert_eq!(route(&config, 1), ["middle", "right"]);
+    }
+
+    #[test]
+    fn other_links_go_to_the_default_monitor() {
+        let mut config = config(&[1], &[("left", &[2])]);
+        config.default_monitor = Some(String::from("right"));
+        assert_eq!(route(&config, 1), ["right"]);
+        assert_eq!(route(&config, 2), ["left"]);
+    }
+
+    #[test]
+    fn missing_default_monitor_falls_back() {
+        let mut config = config(&[1], &[]);
+        config.default_monitor = Some(String::from("unplugged"));
+        assert_eq!(route(&config, 1), MONITORS);
+    }
+
+    #[test]
+    fn everything_gets_other_links_if_every_monitor_has_its_own() {
+        let config = config(&[1], &[("left", &[2]), ("middle", &[3]), ("right", &[4])]);
+        assert_eq!(route(&config, 1), MONITORS);
+    }
+}
@@ -0,0 +1,51 @@
+//! A little box on the wallpaper saying who set it and what it is.
+use serde::{Serialize, Deserialize};
+
+use crate::Config;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    /// Clear of the taskbar's system tray end, usually.
+    #[default]
+    BottomRight,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub enabled: bool,
+    pub corner: Corner,
+    /// Percent.
+    pub opacity: u8,
+    /// Pixels.
+    pub font_size: u8,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            enabled: false,
+            corner: Corner::default(),
+            opacity: 80,
+            font_size: 14,
+        }
+    }
+}
+
+impl Config {
+    /// Monitors can turn it on or off for themselves.
+    pub fn overlay_for(&self, id: &str) -> bool {
+        self.monitor(id)
+            .and_then(|m| m.overlay)
+            .unwrap_or(self.overlay.enabled)
+    }
+
+    /// Whether anywhere shows the overlay, and so whether posts need looking
+    /// up on e621.
+    pub fn overlay_wanted(&self) -> bool {
+        self.overlay.enabled || self.monitors.iter().any(|m| m.overlay == Some(true))
+    }
+}
@@ -0,0 +1,212 @@
+//! Moving settings between machines. Exports are the usual config JSON
+//! wrapped with a little header so we can tell our files apart from random
+//! JSON someone picked by mistake.
+use std::{fs::File, path::Path};
+use anyhow::{Result, Context};
+use serde::{Serialize, Deserialize};
+
+use crate::Config;
+
+const FORMAT: &str = "walltaker-engine";
+
+#[derive(Serialize, Deserialize)]
+struct Export {
+    format: String,
+    version: String,
+    #[serde(default)]
+    links: Vec<usize>,
+    #[serde(default)]
+    config: Option<Config>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum ImportMode {
+    /// Add imported links and profiles to what's already there.
+    Merge,
+    /// Throw the current settings away.
+    Replace,
+}
+
+pub fn export(to: &Path, config: &Config, links_only: bool) -> Result<()> {
+    let export = Export {
+        format: String::from(FORMAT),
+        version: config.version.clone(),
+        links: config.links.clone(),
+        config: if links_only { None } else { Some(config.clone()) },
+    };
+
+    let file = File::create(to)?;
+    serde_json::to_writer_pretty(file, &export)?;
+
+    Ok(())
+}
+
+/// Read an export and fold it into `current`, returning the new config.
+/// `current` is left alone if anything about the file is off.
+pub fn import(from: &Path, current: &Config, mode: ImportMode) -> Result<Config> {
+    let file = File::open(from)?;
+    let export: Export = serde_json::from_reader(file)
+        .context("This doesn't look like a Walltaker Engine export")?;
+    anyhow::ensure!(export.format == FORMAT, "This doesn't look like a Walltaker Engine export");
+
+    let imported = export.config.as_ref();
+    let links = imported.map_or(&export.links, |c| &c.links);
+
+    let mut new = match (mode, imported) {
+        (ImportMode::Replace, Some(imported)) => imported.clone(),
+        (ImportMode::Replace, None) => Config { links: Vec::new(), ..current.clone() },
+        (ImportMode::Merge, _) => current.clone(),
+    };
+
+    if matches!(mode, ImportMode::Merge) {
+        if let Some(imported) = imported {
+            for profile in &imported.profiles {
+                if !new.profiles.iter().any(|p| p.name == profile.name) {
+                    new.profiles.push(profile.clone());
+                }
+            }
+        }
+    }
+
+    for link in links {
+        if !new.links.contains(link) {
+            new.links.push(*link);
+        }
+    }
+
+    if new.active_profile.as_ref().is_some_and(|a| !new.profiles.iter().any(|p| &p.name == a)) {
+        new.active_profile = None;
+    }
+    new.version.clone_from(&current.version);
+
+    let errors = new.validate();
+    if !errors.is_empty() {
+        let errors = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+        anyhow::bail!("{}", errors.join("\n"));
+    }
+
+    Ok(new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{profile::Profile, temp_dir::TempDir};
+
+    fn profile(name: &str, links: &[usize]) -> Profile {
+        Profile {
+            name: String::from(name),
+            links: links.to_vec(),
+            background_colour: String::from("#000000"),
+            ..Default::default()
+        }
+    }
+
+    fn config(links: &[usize], profiles: &[&str]) -> Config {
+        Config {
+            links: links.to_vec(),
+            profiles: profiles.iter().map(|name| profile(name, &[1])).collect(),
+            ..crate::default_config()
+        }
+    }
+
+    /// Somewhere in `dir` to import `export` from.
+    fn exported(dir: &TempDir, export: &Export) -> std::path::PathBuf {
+        let path = dir.join("export.json");
+        serde_json::to_writer(File::create(&path).unwrap(), export).unwrap();
+        path
+    }
+
+    fn full(config: Config) -> Export {
+        Export {
+            format: String::from(FORMAT),
+            version: String::from("v0"),
+            links: config.links.clone(),
+            config: Some(config),
+        }
+    }
+
+    #[test]
+    fn merge_adds_links_and_new_profiles() {
+        let dir = TempDir::new("import-merge");
+        let path = exported(&dir, &full(config(&[2, 3], &["Home", "Work"])));
+        let current = config(&[1, 2], &["Home"]);
+
+        let new = import(&path, &current, ImportMode::Merge).unwrap();
+        assert_eq!(new.links, [1, 2, 3]);
+        let names: Vec<_> = new.profiles.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Home", "Work"]);
+    }
+
+    #[test]
+    fn merge_keeps_existing_profiles() {
+        let mut theirs = config(&[], &[]);
+        theirs.profiles.push(profile("Home", &[9]));
+        let dir = TempDir::new("import-merge-keep");
+        let path = exported(&dir, &full(theirs));
+        let current = config(&[], &["Home"]);
+
+        let new = import(&path, &current, ImportMode::Merge).unwrap();
+        assert_eq!(new.profiles.len(), 1);
+        assert_eq!(new.profiles[0].links, [1]);
+    }
+
+    #[test]
+    fn replace_throws_current_away() {
+        let dir = TempDir::new("import-replace");
+        let path = exported(&dir, &full(config(&[5], &["Work"])));
+        let current = config(&[1, 2], &["Home"]);
+
+        let new = import(&path, &current, ImportMode::Replace).unwrap();
+        assert_eq!(new.links, [5]);
+        assert_eq!(new.profiles.len(), 1);
+        assert_eq!(new.profiles[0].name, "Work");
+        assert_eq!(new.version, current.version);
+    }
+
+    #[test]
+    fn replace_with_links_only_keeps_other_settings() {
+        let dir = TempDir::new("import-replace-links");
+        let path = exported(&dir, &Export {
+            format: String::from(FORMAT),
+            version: String::from("v0"),
+            links: vec![7, 8],
+            config: None,
+        });
+        let current = Config { notifications: false, ..config(&[1], &["Home"]) };
+
+        let new = import(&path, &current, ImportMode::Replace).unwrap();
+        assert_eq!(new.links, [7, 8]);
+        assert!(!new.notifications);
+        assert_eq!(new.profiles.len(), 1);
+    }
+
+    #[test]
+    fn missing_active_profile_is_cleared() {
+        let mut theirs = config(&[], &["Work"]);
+        theirs.active_profile = Some(String::from("Gone"));
+        let dir = TempDir::new("import-active");
+        let path = exported(&dir, &full(theirs));
+
+        let new = import(&path, &config(&[], &[]), ImportMode::Replace).unwrap();
+        assert_eq!(new.active_profile, None);
+    }
+
+    #[test]
+    fn rejects_other_json() {
+        let dir = TempDir::new("import-other");
+        let path = dir.join("export.json");
+        std::fs::write(&path, r#"{"format": "something-else", "version": "1"}"#).unwrap();
+
+        assert!(import(&path, &config(&[1], &[]), ImportMode::Merge).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_imports() {
+        let dir = TempDir::new("import-invalid");
+        let path = exported(&dir, &full(config(&[0], &[])));
+        let current = config(&[1], &[]);
+
+        assert!(import(&path, &current, ImportMode::Replace).is_err());
+    }
+}
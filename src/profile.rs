@@ -0,0 +1,77 @@
+//! Named sets of links and behaviour that can be swapped between from the
+//! tray. The live settings still sit at the top level of [`crate::Config`];
+//! switching profile just copies a profile's fields over them.
+use serde::{Serialize, Deserialize};
+
+use crate::{Config, FitMode};
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    pub links: Vec<usize>,
+    pub fit_mode: FitMode,
+    pub background_colour: String,
+    pub notifications: bool,
+    pub vibrate_for: u16,
+    pub vibration_intensity: u8,
+}
+
+impl Profile {
+    /// Snapshot the live settings in `config` under `name`.
+    pub fn from_config(name: &str, config: &Config) -> Self {
+        Profile {
+            name: String::from(name),
+            links: config.links.clone(),
+            fit_mode: config.fit_mode.clone(),
+            background_colour: config.background_colour.clone(),
+            notifications: config.notifications,
+            vibrate_for: config.vibrate_for,
+            vibration_intensity: config.vibration_intensity,
+        }
+    }
+}
+
+impl Config {
+    /// Make the profile called `name` the live configuration. Returns whether
+    /// there was such a profile.
+    pub fn switch_profile(&mut self, name: &str) -> bool {
+        let Some(profile) = self.profiles.iter().find(|p| p.name == name).cloned() else {
+            return false;
+        };
+
+        self.links = profile.links;
+
+        self.fit_mode = profile.fit_mode;
+        self.background_colour = profile.background_colour;
+        self.notifications = profile.notifications;
+        self.vibrate_for = profile.vibrate_for;
+        self.vibration_intensity = profile.vibration_intensity;
+        self.active_profile = Some(profile.name);
+
+        true
+    }
+
+    /// Write the live settings back into the active profile, if there is one,
+    /// so edits made in the settings window stick to it.
+    pub fn sync_active_profile(&mut self) {
+        let Some(name) = self.active_profile.clone() else { return };
+        let snapshot = Profile::from_config(&name, self);
+
+        if let Some(profile) = self.profiles.iter_mut().find(|p| p.name == name) {
+            *profile = snapshot;
+        } else {
+            // The active profile was deleted from the settings window
+            self.active_profile = None;
+        }
+    }
+}
+
+/// What the tray shows for a profile.
+pub fn tray_label(name: &str, active: Option<&str>) -> String {
+    if active == Some(name) {
+        format!("● {name}")
+    } else {
+        format!("    {name}")
+    }
+}
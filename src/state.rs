@@ -0,0 +1,67 @@
+//! What's on the wallpaper right now, saved between runs so we can put it
+//! back up straight away at startup instead of waiting on Walltaker.
+use std::{collections::HashMap, fs::File, path::Path, time::SystemTime};
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+
+use crate::{e621, walltaker::WallpaperUpdate};
+
+/// What's up on each monitor, by [`Monitor::id`](crate::hwnd::Monitor::id).
+pub type Shown = HashMap<String, Wallpaper>;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Wallpaper {
+    pub url: String,
+    pub link: usize,
+    pub set_by: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub set_at: u64,
+    /// Details from e621, if we looked them up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post: Option<e621::Post>,
+}
+
+impl Wallpaper {
+    pub fn new(url: &str, update: &WallpaperUpdate) -> Self {
+        Wallpaper {
+            url: String::from(url),
+            link: update.id,
+            set_by: update.set_by.clone(),
+            set_at: now(),
+            post: None,
+        }
+    }
+}
+
+pub fn save(to: &Path, shown: &Shown) -> Result<()> {
+    let file = File::create(to)?;
+    serde_json::to_writer(file, shown)?;
+
+    Ok(())
+}
+
+/// The last wallpapers we showed, if we remember any.
+pub fn load(from: &Path) -> Shown {
+    let Ok(file) = File::open(from) else {
+        return Shown::new();
+    };
+
+    match serde_json::from_reader(file) {
+        Ok(shown) => shown,
+        Err(e) => {
+            log::warn!("Couldn't read the saved wallpaper: {e}");
+            Shown::new()
+        },
+    }
+}
+
+/// Whichever of `shown` was set most recently.
+pub fn latest(shown: &Shown) -> Option<&Wallpaper> {
+    shown.values().max_by_key(|w| w.set_at)
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
@@ -0,0 +1,32 @@
+//! Scratch folders for tests that need real files.
+use std::path::{Path, PathBuf};
+
+/// A fresh folder in the system's temp folder, deleted again when dropped.
+/// Named after the test using it so tests can run at the same time.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(test: &str) -> Self {
+        let path = std::env::temp_dir()
+            .join(format!("walltaker-engine-{test}-{}", std::process::id()));
+        // Left over from a run that was killed
+        _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,392 @@
+//! Checks for settings coming from outside (the settings page, imports)
+//! before they're allowed anywhere near the live config.
+use serde::Serialize;
+
+use crate::{background::Adjustments, Config, LoopPolicy, profile::Profile};
+
+pub const MAX_VIBRATE_FOR: u16 = 5000;
+pub const MAX_VIBRATION_INTENSITY: u8 = 100;
+pub const MIN_CACHE_SIZE_MB: u64 = 16;
+pub const MAX_HISTORY: usize = 10_000;
+pub const MAX_TRANSITION_MS: u32 = 10_000;
+pub const MAX_VOLUME: u8 = 100;
+pub const PLAYBACK_SPEED: std::ops::RangeInclusive<f64> = 0.25..=4.;
+pub const MAX_PLAYS: u16 = 1000;
+pub const COLLAGE_SIZE: std::ops::RangeInclusive<u8> = 1..=8;
+pub const MIN_BRIGHTNESS: u8 = 10;
+pub const MAX_BLUR_PX: u8 = 50;
+pub const MAX_AUTO_FIT_THRESHOLD: u8 = 100;
+pub const MAX_OVERLAY_OPACITY: u8 = 100;
+pub const OVERLAY_FONT_SIZE: std::ops::RangeInclusive<u8> = 8..=48;
+pub const LOAD_TIMEOUT_SECS: std::ops::RangeInclusive<u32> = 1..=300;
+
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    /// Dotted path to the offending field, e.g. `profiles.Home.links`.
+    pub field: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Config {
+    /// Every problem with this config. Empty means it's fine.
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        check_links(&mut errors, "links", &self.links);
+        check_colour(&mut errors, "background_colour", &self.background_colour);
+        check_vibration(&mut errors, "", self.vibrate_for, self.vibration_intensity);
+
+        // It's joined onto the themes folder, so it has to stay in there
+        if let Some(theme) = &self.theme {
+            if theme.is_empty() || theme.contains(['/', '\\', ':']) || theme.starts_with('.') {
+                errors.push(FieldError {
+                    field: String::from("theme"),
+                    message: String::from("Themes are picked by their folder name in the themes folder"),
+                });
+            }
+        }
+
+        if self.auto_fit_threshold > MAX_AUTO_FIT_THRESHOLD {
+            errors.push(FieldError {
+                field: String::from("auto_fit_threshold"),
+                message: format!("The auto fit threshold can be at most {MAX_AUTO_FIT_THRESHOLD}%"),
+            });
+        }
+
+        if self.cache_size_mb < MIN_CACHE_SIZE_MB {
+            errors.push(FieldError {
+                field: String::from("cache_size_mb"),
+                message: format!("The media cache needs at least {MIN_CACHE_SIZE_MB}MB"),
+            });
+        }
+
+        if self.history_limit > MAX_HISTORY {
+            errors.push(FieldError {
+                field: String::from("history_limit"),
+                message: format!("History can hold at most {MAX_HISTORY} wallpapers"),
+            });
+        }
+
+        if !LOAD_TIMEOUT_SECS.contains(&self.load_timeout_secs) {
+            errors.push(FieldError {
+                field: String::from("load_timeout_secs"),
+                message: format!("The load timeout has to be between {} and {} seconds",
+                    LOAD_TIMEOUT_SECS.start(), LOAD_TIMEOUT_SECS.end()),
+            });
+        }
+
+        if self.transition_ms > MAX_TRANSITION_MS {
+            errors.push(FieldError {
+                field: String::from("transition_ms"),
+                message: format!("Transitions can last at most {MAX_TRANSITION_MS}ms"),
+            });
+        }
+
+        if self.volume > MAX_VOLUME {
+            errors.push(FieldError {
+                field: String::from("volume"),
+                message: format!("Volume can't go past {MAX_VOLUME}%"),
+            });
+        }
+
+        if !PLAYBACK_SPEED.contains(&self.playback_speed) {
+            errors.push(FieldError {
+                field: String::from("playback_speed"),
+                message: format!("Playback speed has to be between {}x and {}x",
+                    PLAYBACK_SPEED.start(), PLAYBACK_SPEED.end()),
+            });
+        }
+
+        if let LoopPolicy::Times(times) = self.loop_policy {
+            if times == 0 || times > MAX_PLAYS {
+                errors.push(FieldError {
+                    field: String::from("loop_policy"),
+                    message: format!("Videos can be played between 1 and {MAX_PLAYS} times"),
+                });
+            }
+        }
+
+        for (field, size) in [("collage.columns", self.collage.columns), ("collage.rows", self.collage.rows)] {
+            if !COLLAGE_SIZE.contains(&size) {
+                errors.push(FieldError {
+                    field: String::from(field),
+                    message: format!("Collages can be {} to {} tiles across and down",
+                        COLLAGE_SIZE.start(), COLLAGE_SIZE.end()),
+                });
+            }
+        }
+
+        check_adjustments(&mut errors, "adjustments", &self.adjustments);
+
+        if self.overlay.opacity > MAX_OVERLAY_OPACITY {
+            errors.push(FieldError {
+                field: String::from("overlay.opacity"),
+                message: format!("Info opacity can be at most {MAX_OVERLAY_OPACITY}%"),
+            });
+        }
+
+        if !OVERLAY_FONT_SIZE.contains(&self.overlay.font_size) {
+            errors.push(FieldError {
+                field: String::from("overlay.font_size"),
+                message: format!("Info text size must be between {} and {}px",
+                    OVERLAY_FONT_SIZE.start(), OVERLAY_FONT_SIZE.end()),
+            });
+        }
+
+        if self.archive.enabled && self.archive.folder.trim().is_empty() {
+            errors.push(FieldError {
+                field: String::from("archive.folder"),
+                message: String::from("Pick a folder to save wallpapers to"),
+            });
+        }
+
+        if self.archive.template.trim().is_empty() {
+            errors.push(FieldError {
+                field: String::from("archive.template"),
+                message: String::from("The file name template can't be empty"),
+            });
+        }
+
+        for (i, profile) in self.profiles.iter().enumerate() {
+            check_profile(&mut errors, profile);
+
+            if self.profiles[..i].iter().any(|p| p.name == profile.name) {
+                errors.push(FieldError {
+                    field: format!("profiles.{}", profile.name),
+                    message: format!("There are two profiles called {}", profile.name),
+                });
+            }
+        }
+
+        for (i, monitor) in self.monitors.iter().enumerate() {
+            check_links(&mut errors, &format!("monitors.{}.links", monitor.id), &monitor.links);
+            if let Some(adjustments) = &monitor.adjustments {
+                check_adjustments(&mut errors, &format!("monitors.{}.adjustments", monitor.id), adjustments);
+            }
+
+            if let Some(colour) = &monitor.background_colour {
+                check_colour(&mut errors, &format!("monitors.{}.background_colour", monitor.id), colour);
+            }
+
+            if self.monitors[..i].iter().any(|m| m.id == monitor.id) {
+                errors.push(FieldError {
+                    field: format!("monitors.{}", monitor.id),
+                    message: format!("{} has two sets of settings", monitor.name),
+                });
+            }
+        }
+
+        if self.monitors.iter().filter(|m| m.audio).count() > 1 {
+            errors.push(FieldError {
+                field: String::from("monitors"),
+                message: String::from("Only one monitor can play sound"),
+            });
+        }
+
+        if let Some(active) = &self.active_profile {
+            if !self.profiles.iter().any(|p| &p.name == active) {
+                errors.push(FieldError {
+                    field: String::from("active_profile"),
+                    message: format!("There's no profile called {active}"),
+                });
+            }
+        }
+
+        errors
+    }
+}
+
+fn check_adjustments(errors: &mut Vec<FieldError>, field: &str, adjustments: &Adjustments) {
+    if !(MIN_BRIGHTNESS..=100).contains(&adjustments.brightness) {
+        errors.push(FieldError {
+            field: format!("{field}.brightness"),
+            message: format!("Brightness has to be between {MIN_BRIGHTNESS}% and 100%"),
+        });
+    }
+
+    if adjustments.blur_px > MAX_BLUR_PX {
+        errors.push(FieldError {
+            field: format!("{field}.blur_px"),
+            message: format!("Blur can be at most {MAX_BLUR_PX}px"),
+        });
+    }
+
+    if adjustments.tint_strength > 100 || adjustments.grayscale > 100 {
+        errors.push(FieldError {
+            field: String::from(field),
+            message: String::from("Tint and grayscale are percentages"),
+        });
+    }
+
+    check_colour(errors, &format!("{field}.tint"), &adjustments.tint);
+}
+
+fn check_profile(errors: &mut Vec<FieldError>, profile: &Profile) {
+    if profile.name.trim().is_empty() {
+        errors.push(FieldError {
+            field: String::from("profiles"),
+            message: String::from("Profiles need a name"),
+        });
+    }
+
+    let prefix = format!("profiles.{}.", profile.name);
+    check_links(errors, &format!("{prefix}links"), &profile.links);
+    check_colour(errors, &format!("{prefix}background_colour"), &profile.background_colour);
+    check_vibration(errors, &prefix, profile.vibrate_for, profile.vibration_intensity);
+}
+
+fn check_links(errors: &mut Vec<FieldError>, field: &str, links: &[usize]) {
+    if links.contains(&0) {
+        errors.push(FieldError {
+            field: String::from(field),
+            message: String::from("0 isn't a valid link"),
+        });
+    }
+
+    for (i, link) in links.iter().enumerate() {
+        if links[..i].contains(link) {
+            errors.push(FieldError {
+                field: String::from(field),
+                message: format!("Link {link} is in there twice"),
+            });
+        }
+    }
+}
+
+fn check_colour(errors: &mut Vec<FieldError>, field: &str, colour: &str) {
+    if !is_hex_colour(colour) {
+        errors.push(FieldError {
+            field: String::from(field),
+            message: format!("{colour:?} isn't a colour (expected something like #202640)"),
+        });
+    }
+}
+
+fn check_vibration(errors: &mut Vec<FieldError>, prefix: &str, duration: u16, intensity: u8) {
+    if duration > MAX_VIBRATE_FOR {
+        errors.push(FieldError {
+            field: format!("{prefix}vibrate_for"),
+            message: format!("Vibration can last at most {MAX_VIBRATE_FOR}ms"),
+        });
+    }
+
+    if intensity > MAX_VIBRATION_INTENSITY {
+        errors.push(FieldError {
+            field: format!("{prefix}vibration_intensity"),
+            message: format!("Vibration intensity can be at most {MAX_VIBRATION_INTENSITY}%"),
+        });
+    }
+}
+
+/// `#rgb` or `#rrggbb`, which is all the colour picker ever gives us.
+pub fn is_hex_colour(colour: &str) -> bool {
+    colour.strip_prefix('#').is_some_and(|hex|
+        matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor;
+
+    fn fields(config: &Config) -> Vec<String> {
+        config.validate().into_iter().map(|e| e.field).collect()
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(crate::default_config().validate().is_empty());
+    }
+
+    #[test]
+    fn bad_links() {
+        let config = Config { links: vec![0, 5, 5], ..crate::default_config() };
+        assert_eq!(fields(&config), ["links", "links"]);
+    }
+
+    #[test]
+    fn bad_colour() {
+        for colour in ["", "202640", "#20264", "#gggggg", "red", "#202640; x"] {
+            let config = Config { background_colour: String::from(colour), ..crate::default_config() };
+            assert_eq!(fields(&config), ["background_colour"], "{colour:?}");
+        }
+    }
+
+    #[test]
+    fn hex_colours() {
+        assert!(is_hex_colour("#202640"));
+        assert!(is_hex_colour("#FFF"));
+        assert!(!is_hex_colour("#ffff"));
+    }
+
+    #[test]
+    fn out_of_range_numbers() {
+        let config = Config {
+            vibrate_for: MAX_VIBRATE_FOR + 1,
+            vibration_intensity: MAX_VIBRATION_INTENSITY + 1,
+            cache_size_mb: MIN_CACHE_SIZE_MB - 1,
+            history_limit: MAX_HISTORY + 1,
+            load_timeout_secs: 0,
+            transition_ms: MAX_TRANSITION_MS + 1,
+            volume: MAX_VOLUME + 1,
+            playback_speed: 10.,
+            loop_policy: LoopPolicy::Times(0),
+            ..crate::default_config()
+        };
+
+        assert_eq!(fields(&config), [
+            "vibrate_for", "vibration_intensity", "cache_size_mb", "history_limit",
+            "load_timeout_secs", "transition_ms", "volume", "playback_speed", "loop_policy",
+        ]);
+    }
+
+    #[test]
+    fn themes_stay_in_their_folder() {
+        for theme in ["", "..", "../x", "a/b", "a\\b", "C:x", ".hidden"] {
+            let config = Config { theme: Some(String::from(theme)), ..crate::default_config() };
+            assert_eq!(fields(&config), ["theme"], "{theme:?}");
+        }
+
+        let config = Config { theme: Some(String::from("neon")), ..crate::default_config() };
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn profiles() {
+        let profile = |name: &str, colour: &str| Profile {
+            name: String::from(name),
+            background_colour: String::from(colour),
+            ..Default::default()
+        };
+        let config = Config {
+            profiles: vec![profile("Home", "#000"), profile("Home", "#000"), profile(" ", "nope")],
+            active_profile: Some(String::from("Work")),
+            ..crate::default_config()
+        };
+
+        assert_eq!(fields(&config), [
+            "profiles.Home", "profiles", "profiles. .background_colour", "active_profile",
+        ]);
+    }
+
+    #[test]
+    fn monitors() {
+        let monitor = |id: &str| monitor::Settings {
+            id: String::from(id),
+            audio: true,
+            ..Default::default()
+        };
+        let config = Config {
+            monitors: vec![monitor("a"), monitor("a")],
+            ..crate::default_config()
+        };
+
+        assert_eq!(fields(&config), ["monitors.a", "monitors"]);
+    }
+}
@@ -19,7 +19,7 @@ pub enum Incoming {
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct WallpaperUpdate {
     pub id:       usize,
     pub post_url: Option<String>,
@@ -2,13 +2,14 @@
 /// <https://github.com/wravery/webview2-rs>
 /// I'm thrilled to not have to do this work myself.
 use std::{
+    cell::Cell,
     collections::HashMap,
     fmt, mem, ptr,
     sync::{mpsc, Arc, Mutex}, rc::Rc,
 };
 
-use serde::Deserialize;
-use serde_json::Value;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
 #[allow(clippy::wildcard_imports)]
 use webview2_com::{*, Microsoft::Web::WebView2::Win32::*};
 use windows::Win32::{
@@ -22,7 +23,7 @@ use windows::Win32::{
 use windows::{
     core::*,
     Win32::{
-        Foundation::{E_POINTER, HWND, LPARAM, LRESULT, RECT, SIZE, WPARAM},
+        Foundation::{BOOL, E_POINTER, HWND, LPARAM, LRESULT, RECT, SIZE, WPARAM},
         Graphics::Gdi,
         System::{LibraryLoader, Threading, WinRT::EventRegistrationToken},
         UI::WindowsAndMessaging::{self,
@@ -33,6 +34,7 @@ use windows::{
     },
 };
 
+pub mod bindings;
 pub mod settings;
 pub mod webviews;
 
@@ -43,13 +45,40 @@ pub enum Error {
     Windows(windows::core::Error),
     Json(serde_json::Error),
     Lock,
+    /// The page called a binding with arguments that don't fit its signature.
+    Arguments { method: String, message: String },
+    /// A page couldn't be loaded.
+    Navigation { url: String },
+    /// What's bound doesn't match `src/webview/bindings.rs`.
+    Bindings(String),
 }
 
 impl std::error::Error for Error { }
 
+impl Error {
+    /// What a binding's promise gets rejected with.
+    fn to_js(&self) -> Value {
+        match self {
+            Error::Arguments { method, message } => json!({
+                "kind": "arguments",
+                "method": method,
+                "message": message,
+            }),
+            other => json!({
+                "kind": "internal",
+                "message": other.to_string(),
+            }),
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{self:?}")
+        match self {
+            Error::Navigation { url } => write!(f, "Couldn't load {url}"),
+            Error::Bindings(problems) => write!(f, "Bindings don't match src/webview/bindings.rs: {problems}"),
+            other => write!(f, "{other:?}"),
+        }
     }
 }
 
@@ -157,6 +186,8 @@ type WebViewSender = mpsc::Sender<Box<dyn FnOnce(WebView) + Send>>;
 type WebViewReceiver = mpsc::Receiver<Box<dyn FnOnce(WebView) + Send>>;
 type BindingCallback = Box<dyn FnMut(Vec<Value>) -> Result<Value>>;
 type BindingsMap = HashMap<String, BindingCallback>;
+/// What [`WebView::bind_typed`] bindings take and return, by name.
+type BindingTypes = HashMap<String, (String, String)>;
 
 #[derive(Clone)]
 pub struct WebView {
@@ -169,13 +200,18 @@ pub struct WebView {
     min_h: i32,
     html: Arc<Mutex<String>>,
     bindings: Rc<Mutex<BindingsMap>>,
+    binding_types: Rc<Mutex<BindingTypes>>,
     frame: Option<FrameWindow>,
     parent: Arc<HWND>,
+    /// The handler for bindings holds a clone of the webview, so it has to be
+    /// removed before the controller can ever be dropped.
+    message_token: Rc<Cell<Option<EventRegistrationToken>>>,
 }
 
 impl Drop for WebViewController {
     fn drop(&mut self) {
-        unsafe { self.0.Close() }.unwrap();
+        // Fails if it was already closed, which is fine
+        _ = unsafe { self.0.Close() };
     }
 }
 
@@ -294,8 +330,10 @@ impl WebView {
             min_h: min_size.1,
             html: Mutex::new(String::new()).into(),
             bindings: Rc::new(Mutex::new(HashMap::new())),
+            binding_types: Rc::new(Mutex::new(HashMap::new())),
             frame,
             parent: Arc::new(parent),
+            message_token: Rc::new(Cell::new(None)),
         };
 
         // Inject the invoke handler.
@@ -322,7 +360,7 @@ impl WebView {
                                             Err(err) => bound.resolve(
                                                 value.id,
                                                 1,
-                                                &Value::String(format!("{err:#?}")),
+                                                &err.to_js(),
                                             ),
                                         };
                                     }
@@ -334,6 +372,7 @@ impl WebView {
                 })),
                 &mut token_,
             )?;
+            webview.message_token.set(Some(token_));
         }
 
         if webview.frame.is_some() {
@@ -391,6 +430,20 @@ impl WebView {
         }
     }
 
+    /// Tear the webview down now rather than whenever the last clone goes.
+    /// Anything else still holding a clone is left with a dead webview.
+    pub fn close(&self) -> Result<()> {
+        if let Some(token) = self.message_token.take() {
+            unsafe { self.webview.remove_WebMessageReceived(token)? };
+        }
+        if self.frame.is_some() {
+            WebView::set_window_webview(self.get_window(), None);
+        }
+        unsafe { self.controller.0.Close()? };
+
+        Ok(())
+    }
+
     pub fn init(&self, js: &str) -> Result<&Self> {
         let webview = self.webview.clone();
         let js = String::from(js);
@@ -434,6 +487,36 @@ impl WebView {
         Ok(self)
     }
 
+    /// Load `url`, waiting until it's loaded (or failed to).
+    pub fn navigate(&self, url: &str) -> Result<&Self> {
+        let webview = self.webview.as_ref();
+        let (tx, rx) = mpsc::channel();
+
+        let handler =
+            NavigationCompletedEventHandler::create(Box::new(move |_sender, args| {
+                let mut success = BOOL::default();
+                if let Some(args) = args {
+                    unsafe { args.IsSuccess(&mut success)?; }
+                }
+                tx.send(success.as_bool()).expect("send over mpsc channel");
+                Ok(())
+            }));
+        let mut token = EventRegistrationToken::default();
+        let success = unsafe {
+            webview.add_NavigationCompleted(&handler, &mut token)?;
+            webview.Navigate(&HSTRING::from(url))?;
+            let result = webview2_com::wait_with_pump(rx);
+            webview.remove_NavigationCompleted(token)?;
+            result?
+        };
+
+        if !success {
+            return Err(Error::Navigation { url: String::from(url) });
+        }
+
+        Ok(self)
+    }
+
     pub fn eval(&self, js: &str) -> Result<&Self> {
         let webview = self.webview.clone();
         let js = String::from(js);
@@ -449,6 +532,37 @@ impl WebView {
         Ok(self)
     }
 
+    /// Serve the files in `folder` to the page at `https://{host}/`.
+    pub fn map_folder(&self, host: &str, folder: &std::path::Path) -> Result<&Self> {
+        unsafe {
+            self.webview.SetVirtualHostNameToFolderMapping(
+                &HSTRING::from(host),
+                &HSTRING::from(folder.as_os_str()),
+                COREWEBVIEW2_HOST_RESOURCE_ACCESS_KIND_ALLOW,
+            )?;
+        }
+
+        Ok(self)
+    }
+
+    pub fn set_muted(&self, muted: bool) -> Result<&Self> {
+        unsafe { self.webview.SetIsMuted(muted)?; }
+
+        Ok(self)
+    }
+
+    /// Send `message` to the page as JSON. It arrives as the `data` of a
+    /// `message` event on `window.chrome.webview`.
+    pub fn post_message<T: serde::Serialize>(&self, message: &T) -> Result<&Self> {
+        let json = serde_json::to_string(message)?;
+        unsafe {
+            let json = CoTaskMemPWSTR::from(json.as_str());
+            self.webview.PostWebMessageAsJson(*json.as_ref().as_pcwstr())?;
+        }
+
+        Ok(self)
+    }
+
     pub fn get_window(&self) -> HWND {
         *self.parent
     }
@@ -513,6 +627,63 @@ impl WebView {
         self.init(&js)
     }
 
+    /// [`WebView::bind`] with the (de)serializing done for you. The page calls
+    /// it with no arguments for `()`, one argument for anything else, or
+    /// several for a tuple.
+    pub fn bind_typed<A, R, F>(&self, name: &str, mut f: F) -> Result<&Self>
+    where
+        A: DeserializeOwned,
+        R: Serialize,
+        F: FnMut(A) -> Result<R> + 'static,
+    {
+        self.binding_types.lock()?.insert(String::from(name), (
+            short_type_name(std::any::type_name::<A>()),
+            short_type_name(std::any::type_name::<R>()),
+        ));
+
+        let method = String::from(name);
+        self.bind(name, move |mut params| {
+            let args = match params.len() {
+                0 => Value::Null,
+                1 => params.remove(0),
+                _ => Value::Array(params),
+            };
+
+            let args = serde_json::from_value(args).map_err(|e| Error::Arguments {
+                method: method.clone(),
+                message: e.to_string(),
+            })?;
+
+            Ok(serde_json::to_value(f(args)?)?)
+        })
+    }
+
+    /// Make sure what's been bound with [`WebView::bind_typed`] is exactly
+    /// `declared`, types and all.
+    pub fn check_bound(&self, declared: &[bindings::Binding]) -> Result<()> {
+        let bound = self.binding_types.lock()?;
+        let mut problems = Vec::new();
+
+        for (name, params, returns) in declared {
+            let takes = argument_type(params);
+            match bound.get(*name) {
+                None => problems.push(format!("{name} isn't bound")),
+                Some((a, r)) if *a != takes || r != returns =>
+                    problems.push(format!("{name} takes {a} and returns {r}, not {takes} and {returns}")),
+                Some(_) => { },
+            }
+        }
+        for name in bound.keys().filter(|name| !declared.iter().any(|(d, ..)| d == name)) {
+            problems.push(format!("{name} isn't declared"));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Bindings(problems.join("; ")))
+        }
+    }
+
     pub fn resolve(&self, id: u64, status: i32, result: &Value) -> &Self {
         let result = result.to_string();
 
@@ -638,4 +809,57 @@ unsafe fn SetWindowLong(window: HWND, index: WINDOW_LONG_PTR_INDEX, value: isize
 #[cfg(target_pointer_width = "64")]
 unsafe fn GetWindowLong(window: HWND, index: WINDOW_LONG_PTR_INDEX) -> isize {
     WindowsAndMessaging::GetWindowLongPtrW(window, index)
-}
\ No newline at end of file
+}
+
+/// What [`WebView::bind_typed`] deserializes the page's arguments as, given
+/// the declared ones.
+fn argument_type(params: &[(&str, &str)]) -> String {
+    match params {
+        [] => String::from("()"),
+        [(_, rust)] => String::from(*rust),
+        _ => format!("({})", params.iter().map(|(_, rust)| *rust).collect::<Vec<_>>().join(", ")),
+    }
+}
+
+/// [`std::any::type_name`] without the paths, so `alloc::vec::Vec<u8>` is
+/// `Vec<u8>`.
+fn short_type_name(name: &str) -> String {
+    let mut parts: Vec<_> = name.split("::").collect();
+    let last = parts.pop().unwrap_or_default();
+
+    parts.into_iter()
+        .map(|part| part.trim_end_matches(|c: char| c.is_ascii_alphanumeric() || c == '_'))
+        .chain([last])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_type_names() {
+        assert_eq!(short_type_name(std::any::type_name::<()>()), "()");
+        assert_eq!(short_type_name(std::any::type_name::<Option<String>>()), "Option<String>");
+        assert_eq!(short_type_name(std::any::type_name::<(String, u64)>()), "(String, u64)");
+        assert_eq!(short_type_name(std::any::type_name::<Vec<crate::validation::FieldError>>()),
+            "Vec<FieldError>");
+    }
+
+    #[test]
+    fn argument_types_match_bind_typed() {
+        assert_eq!(argument_type(&[]), "()");
+        assert_eq!(argument_type(&[("md5", "String")]), "String");
+        assert_eq!(argument_type(&[("url", "String"), ("setAt", "u64")]), "(String, u64)");
+    }
+
+    /// Every declared type has to survive [`short_type_name`] to match.
+    #[test]
+    fn declared_types_are_short() {
+        for (name, params, returns) in bindings::SETTINGS.iter().chain(bindings::BACKGROUND) {
+            for rust in params.iter().map(|(_, rust)| rust).chain([returns]) {
+                assert_eq!(short_type_name(rust), *rust, "{name}");
+            }
+        }
+    }
+}
@@ -0,0 +1,30 @@
+// Everything the pages can call, as (name, arguments, what it resolves to),
+// in Rust types. build.rs checks the pages against these and that
+// res/bindings.d.ts matches them, and the webviews check they're what
+// actually got bound. No `use`s or inner docs here, since build.rs
+// `include!`s it.
+
+pub type Binding = (&'static str, &'static [(&'static str, &'static str)], &'static str);
+
+pub const SETTINGS: &[Binding] = &[
+    ("saveSettings", &[("settings", "Config")], "Vec<FieldError>"),
+    ("testNotifications", &[], "()"),
+    ("loadSettings", &[], "Config"),
+    ("exportSettings", &[("linksOnly", "bool")], "String"),
+    ("importSettings", &[("mode", "ImportMode")], "String"),
+    ("loadHistory", &[], "Value"),
+    ("revertTo", &[("url", "String"), ("setAt", "u64")], "()"),
+    ("openPost", &[("md5", "String")], "()"),
+    ("loadFavorites", &[], "Value"),
+    ("removeFavorite", &[("md5", "String")], "()"),
+    ("applyFavorite", &[("md5", "String")], "()"),
+    ("loadMonitors", &[], "Vec<Monitor>"),
+    ("loadThemes", &[], "Vec<String>"),
+    ("pickFolder", &[], "Option<String>"),
+];
+
+/// Themes get these too. See `res/themes.md`.
+pub const BACKGROUND: &[Binding] = &[
+    ("mediaFailed", &[("url", "String"), ("reason", "String")], "()"),
+    ("themeFailed", &[("reason", "String")], "()"),
+];
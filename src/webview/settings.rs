@@ -1,8 +1,16 @@
+use std::path::Path;
 use std::rc::Rc;
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc};
+use serde::Serialize;
 use tokio::sync::Mutex;
 
-use crate::webview::{Error, WebView};
+use crate::cache::{self, MediaCache};
+use crate::dialog;
+use crate::favorites::{self, Favorites};
+use crate::history::{self, History};
+use crate::hwnd::Monitor;
+use crate::portable::{self, ImportMode};
+use crate::webview::WebView;
 
 const SETTINGS_HTML: &str = include_str!(concat!(env!("OUT_DIR"), "/settings.html.min"));
 
@@ -11,10 +19,33 @@ pub enum UiMessage {
     UpdateSettings,
     SubscribeTo(usize),
     UnsubscribeFrom(usize),
+    Revert(history::Entry),
+    OpenPost(String),
+    ApplyFavorite(String),
+}
+
+#[derive(Serialize)]
+struct FavoriteItem<'a> {
+    #[serde(flatten)]
+    favorite: &'a favorites::Favorite,
+    thumbnail: String,
+}
+
+#[derive(Serialize)]
+struct HistoryItem<'a> {
+    #[serde(flatten)]
+    entry: &'a history::Entry,
+    /// Local copy if it's still in the cache.
+    thumbnail: String,
 }
 
 pub fn create_settings_webview(
     config: &Rc<Mutex<crate::Config>>,
+    history: &Rc<Mutex<History>>,
+    favorites: &Rc<Mutex<Favorites>>,
+    cache: &Arc<MediaCache>,
+    monitors: &Rc<Mutex<Vec<Monitor>>>,
+    themes: &Path,
 ) -> anyhow::Result<(WebView, mpsc::Receiver<UiMessage>)>
 {
     let (ui_tx, ui_rx) = mpsc::sync_channel(50);
@@ -22,53 +53,198 @@ pub fn create_settings_webview(
     let ui_tx_ = ui_tx.clone();
     
     let settings = WebView::create(None, false, (420, 440))?;
-    settings.bind("saveSettings", move |request| {
-        if let Some(new_cfg) = request.first() {
-            let new_settings: crate::Config = serde_json::from_value(new_cfg.clone())?;
-            let mut config = tokio::task::block_in_place(|| config_.blocking_lock());
+    settings.map_folder(cache::HOST, cache.dir())?;
+    let favorites_dir = tokio::task::block_in_place(|| favorites.blocking_lock().dir().to_path_buf());
+    settings.map_folder(favorites::HOST, &favorites_dir)?;
+    settings.bind_typed("saveSettings", move |new_settings: crate::Config| {
+        // Hand the problems back to the page rather than letting them in
+        let errors = new_settings.validate();
+        if !errors.is_empty() {
+            log::warn!("Rejected settings: {errors:?}");
+            return Ok(errors);
+        }
+
+        let mut config = tokio::task::block_in_place(|| config_.blocking_lock());
+        replace_config(&mut config, new_settings, &ui_tx_);
+
+        Ok(errors)
+    })?;
+
+    let ui_tx_ = ui_tx.clone();
+    settings.bind_typed("testNotifications", move |(): ()| {
+        _ = ui_tx_.send(UiMessage::TestNotification);
+        Ok(())
+    })?;
+
+    let config_ = Rc::clone(config);
+    settings.bind_typed("loadSettings", move |(): ()| {
+        tokio::task::block_in_place(|| Ok(config_.blocking_lock().clone()))
+    })?;
 
-            // This is theoretically really, really slow but these vecs will only
-            // ever contain like, 5 elements tops. So it doesn't really matter.
-            let added = new_settings.links.iter()
-                .filter(|i| !config.links.contains(i));
-            let removed = config.links.iter()
-                .filter(|i| !new_settings.links.contains(i));
+    let config_ = Rc::clone(config);
+    let hwnd = settings.get_window();
+    settings.bind_typed("exportSettings", move |links_only: bool| {
+        let Some(path) = dialog::pick_file(hwnd, true) else {
+            return Ok(String::from("cancelled"));
+        };
 
-            for link in added {
-                _ = ui_tx_.send(UiMessage::SubscribeTo(*link));
-            }
+        let config = tokio::task::block_in_place(|| config_.blocking_lock());
+        Ok(match portable::export(&path, &config, links_only) {
+            Ok(()) => {
+                log::info!("Exported settings to {}", path.display());
+                String::from("ok")
+            },
+            Err(e) => format!("Couldn't export settings: {e}"),
+        })
+    })?;
+
+    let config_ = Rc::clone(config);
+    let ui_tx_ = ui_tx.clone();
+    settings.bind_typed("importSettings", move |mode: ImportMode| {
+        let Some(path) = dialog::pick_file(hwnd, false) else {
+            return Ok(String::from("cancelled"));
+        };
 
-            for link in removed {
-                _ = ui_tx_.send(UiMessage::UnsubscribeFrom(*link));
-            }
+        let mut config = tokio::task::block_in_place(|| config_.blocking_lock());
+        Ok(match portable::import(&path, &config, mode) {
+            Ok(new_settings) => {
+                log::info!("Imported settings from {} ({mode:?})", path.display());
+                replace_config(&mut config, new_settings, &ui_tx_);
+                String::from("ok")
+            },
+            Err(e) => format!("Couldn't import settings: {e:#}"),
+        })
+    })?;
 
-            _ = ui_tx_.send(UiMessage::UpdateSettings);
+    let history_ = Rc::clone(history);
+    let cache_ = Arc::clone(cache);
+    settings.bind_typed("loadHistory", move |(): ()| {
+        let history = tokio::task::block_in_place(|| history_.blocking_lock());
+        let items = history.entries().iter()
+            .map(|entry| HistoryItem {
+                entry,
+                thumbnail: cache_.cached(&entry.url)
+                    .and_then(|path| MediaCache::local_url(&path))
+                    .unwrap_or_else(|| entry.url.clone()),
+            })
+            .collect::<Vec<_>>();
 
-            log::info!("Settings updated {new_settings:#?}");
+        Ok(serde_json::to_value(items)?)
+    })?;
 
-            *config = new_settings;
-            return Ok(serde_json::Value::String(String::from("ok")));
+    let history_ = Rc::clone(history);
+    let ui_tx_ = ui_tx.clone();
+    // Entries are picked by what they are rather than where, since new ones
+    // can come in while the page is open
+    settings.bind_typed("revertTo", move |(url, set_at): (String, u64)| {
+        let history = tokio::task::block_in_place(|| history_.blocking_lock());
+        if let Some(entry) = history.entries().iter().find(|e| e.url == url && e.set_at == set_at) {
+            _ = ui_tx_.send(UiMessage::Revert(entry.clone()));
         }
 
-        Err(Error::WebView2(
-            webview2_com::Error::CallbackError(String::from("Called wrong. wtf?"))))
+        Ok(())
     })?;
+
     let ui_tx_ = ui_tx.clone();
-    settings.bind("testNotifications", move |_| {
-        _ = ui_tx_.send(UiMessage::TestNotification);
-        Ok(serde_json::Value::String(String::from("ok")))
+    settings.bind_typed("openPost", move |md5: String| {
+        // It's going in a URL
+        if md5.chars().all(|c| c.is_ascii_hexdigit()) {
+            _ = ui_tx_.send(UiMessage::OpenPost(md5));
+        }
+
+        Ok(())
     })?;
 
-    let config_ = Rc::clone(config);
-    settings.bind("loadSettings", move |_request| {
-        tokio::task::block_in_place(|| {
-            let cfg = &*config_.blocking_lock();
-            Ok(serde_json::to_value(cfg)?)
-        })
+    let favorites_ = Rc::clone(favorites);
+    settings.bind_typed("loadFavorites", move |(): ()| {
+        let favorites = tokio::task::block_in_place(|| favorites_.blocking_lock());
+        let items = favorites.entries().iter()
+            .map(|favorite| FavoriteItem {
+                favorite,
+                thumbnail: format!("https://{}/{}", favorites::HOST, favorite.file),
+            })
+            .collect::<Vec<_>>();
+
+        Ok(serde_json::to_value(items)?)
+    })?;
+
+    let favorites_ = Rc::clone(favorites);
+    settings.bind_typed("removeFavorite", move |md5: String| {
+        let mut favorites = tokio::task::block_in_place(|| favorites_.blocking_lock());
+        if let Err(e) = favorites.remove(&md5) {
+            log::warn!("Couldn't remove favorite {md5}: {e:#}");
+        }
+
+        Ok(())
+    })?;
+
+    let ui_tx_ = ui_tx.clone();
+    settings.bind_typed("applyFavorite", move |md5: String| {
+        _ = ui_tx_.send(UiMessage::ApplyFavorite(md5));
+        Ok(())
+    })?;
+
+    let monitors_ = Rc::clone(monitors);
+    settings.bind_typed("loadMonitors", move |(): ()| {
+        tokio::task::block_in_place(|| Ok(monitors_.blocking_lock().clone()))
+    })?;
+
+    // Anything in the themes folder with a page to load
+    let themes = themes.to_path_buf();
+    settings.bind_typed("loadThemes", move |(): ()| {
+        let mut names: Vec<_> = std::fs::read_dir(&themes).into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.path().join("index.html").is_file())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect();
+        names.sort();
+
+        Ok(names)
+    })?;
+
+    settings.bind_typed("pickFolder", move |(): ()| {
+        Ok(dialog::pick_folder(hwnd).map(|path| path.display().to_string()))
     })?;
 
+    // build.rs checks settings.html against the declared bindings, so make
+    // sure they're what's actually bound above
+    settings.check_bound(super::bindings::SETTINGS)?;
+
     settings.resize(420, 420)?;
     settings.navigate_html(SETTINGS_HTML)?;
 
     Ok((settings, ui_rx))
-}
\ No newline at end of file
+}
+
+/// Swap `new_settings` in as the live config, telling the main loop what to
+/// (un)subscribe from and that display settings need reapplying.
+fn replace_config(
+    config: &mut crate::Config,
+    mut new_settings: crate::Config,
+    ui_tx: &mpsc::SyncSender<UiMessage>,
+) {
+    new_settings.sync_active_profile();
+
+    // This is theoretically really, really slow but these vecs will only
+    // ever contain like, 5 elements tops. So it doesn't really matter.
+    let (old_links, new_links) = (config.subscribed_links(), new_settings.subscribed_links());
+    let added = new_links.iter()
+        .filter(|i| !old_links.contains(i));
+    let removed = old_links.iter()
+        .filter(|i| !new_links.contains(i));
+
+    for link in added {
+        _ = ui_tx.send(UiMessage::SubscribeTo(*link));
+    }
+
+    for link in removed {
+        _ = ui_tx.send(UiMessage::UnsubscribeFrom(*link));
+    }
+
+    _ = ui_tx.send(UiMessage::UpdateSettings);
+
+    log::info!("Settings updated {new_settings:#?}");
+
+    *config = new_settings;
+}